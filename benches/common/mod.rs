@@ -0,0 +1,89 @@
+//! Synthetic sources for the benchmarks
+//!
+//! Every shape is a chain of functions, each calling the next down to a constant, so a generated
+//! source compiles as well as lexes and parses.
+
+use criterion::BenchmarkId;
+
+/// Sizes of generated sources, in bytes.
+pub const SIZES: [usize; 3] = [64 << 10, 1 << 20, 4 << 20];
+
+#[derive(Debug, Clone, Copy)]
+pub enum Shape {
+    /// Short functions with ASCII names, the common case.
+    Functions,
+    /// Functions under a few lines of comments each.
+    Comments,
+    /// Functions with accented names and comments with emoji, off the ASCII fast paths.
+    Unicode,
+}
+
+pub const SHAPES: [Shape; 3] = [Shape::Functions, Shape::Comments, Shape::Unicode];
+
+impl Shape {
+    pub fn name(self) -> &'static str {
+        match self {
+            Shape::Functions => "functions",
+            Shape::Comments => "comments",
+            Shape::Unicode => "unicode",
+        }
+    }
+
+    fn function_name(self, i: usize) -> String {
+        match self {
+            Shape::Unicode => format!("fonction_été_{}", i),
+            _ => format!("function_{}", i),
+        }
+    }
+
+    fn function(self, i: usize, body: &str) -> String {
+        let name = self.function_name(i);
+        match self {
+            Shape::Functions => format!("pub func {}(): int {{\n    {}\n}}\n\n", name, body),
+            Shape::Comments => format!(
+                "// Function number {}, which is documented at some length so that comments\n\
+                 // make up most of the file, as they do in generated code.\n\
+                 func {}(): int {{ {} }}\n",
+                i, name, body
+            ),
+            Shape::Unicode => format!(
+                "// ✨ résultat numéro {} ✨\nfunc {}(): int {{\n    {}\n}}\n",
+                i, name, body
+            ),
+        }
+    }
+
+    /// Generates a program of about `size` bytes, whose `main` calls the first function.
+    pub fn generate(self, size: usize) -> String {
+        let mut text = format!("func main(): int {{ {}() }}\n\n", self.function_name(0));
+        let mut i = 0;
+        while text.len() < size {
+            let call = format!("{}()", self.function_name(i + 1));
+            text.push_str(&self.function(i, &call));
+            i += 1;
+        }
+        text.push_str(&self.function(i, &i.to_string()));
+        text
+    }
+}
+
+/// Generates a source for each shape and size, with the id of a benchmark over it.
+pub fn sources() -> Vec<(BenchmarkId, String)> {
+    let mut sources = Vec::new();
+    for shape in SHAPES {
+        for size in SIZES {
+            let id = BenchmarkId::new(shape.name(), size_name(size));
+            sources.push((id, shape.generate(size)));
+        }
+    }
+    sources
+}
+
+/// Describes a size for a benchmark name, like `64KiB`.
+fn size_name(size: usize) -> String {
+    if size >= 1 << 20 {
+        format!("{}MiB", size >> 20)
+    } else {
+        format!("{}KiB", size >> 10)
+    }
+}
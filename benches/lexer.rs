@@ -0,0 +1,121 @@
+//! Lexing generated sources, directly and through a `TokenBuffer`
+
+mod common;
+
+use criterion::{
+    criterion_group, criterion_main, measurement::WallTime, BenchmarkGroup, Criterion, Throughput,
+};
+use spuzc::{
+    text::{CharClass, Document, Span, Window},
+    tokens::{self, TokenBuffer, TokenError},
+};
+
+/// Runs `next_token` over each source, through `tokenize`.
+fn lex(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lex");
+    group.sample_size(10);
+    for (id, text) in common::sources() {
+        let document = Document::new(text.as_str());
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_function(id, |b| b.iter(|| tokens::tokenize(&document)));
+    }
+    group.finish();
+}
+
+/// Peeks at and takes every token from a `TokenBuffer` the way the parser does, so the difference
+/// from `lex` is the overhead of buffering.
+fn token_buffer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("token_buffer");
+    group.sample_size(10);
+    for (id, text) in common::sources() {
+        let document = Document::new(text.as_str());
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_function(id, |b| {
+            b.iter(|| {
+                let mut buffer = TokenBuffer::new(Window::new(&document));
+                let mut tokens = Vec::new();
+                loop {
+                    match buffer.peek() {
+                        Ok(_) => tokens.push(buffer.take_token().unwrap()),
+                        Err(TokenError::EndOfFile) => break,
+                        Err(e) => panic!("generated source doesn't lex: {}", e),
+                    }
+                }
+                (tokens, buffer.take_comments())
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Scans one long run of each kind of character the lexer skips over with `take_while`.
+fn take_while(c: &mut Criterion) {
+    let mut group = c.benchmark_group("take_while");
+    group.sample_size(10);
+    compare_scans(
+        &mut group,
+        "whitespace",
+        &"    \n\t\t\r\n".repeat(1 << 16),
+        CharClass::WHITESPACE,
+        char::is_whitespace,
+    );
+    compare_scans(
+        &mut group,
+        "identifier",
+        &"abc_DEF_123_".repeat(1 << 16),
+        CharClass::new(
+            b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ_abcdefghijklmnopqrstuvwxyz",
+            unicode_ident::is_xid_continue,
+        ),
+        unicode_ident::is_xid_continue,
+    );
+    compare_scans(
+        &mut group,
+        "comment",
+        &"a comment; (with) {punctuation}. ".repeat(1 << 15),
+        CharClass::all_but(b"\n\r", |c| {
+            !matches!(c, '\u{85}' | '\u{2028}' | '\u{2029}')
+        }),
+        |c| !matches!(c, '\n' | '\r' | '\u{85}' | '\u{2028}' | '\u{2029}'),
+    );
+    group.finish();
+}
+
+/// Scans `text`, all of which `class` and `function` match, by looking bytes up in the class, by
+/// testing each byte with the function, and a character at a time with `peek` and `take`.
+fn compare_scans(
+    group: &mut BenchmarkGroup<WallTime>,
+    name: &str,
+    text: &str,
+    class: CharClass,
+    function: fn(char) -> bool,
+) {
+    let document = Document::new(text);
+    group.throughput(Throughput::Bytes(text.len() as u64));
+    group.bench_function(format!("{}/class", name), |b| {
+        b.iter(|| scan(&document, |window| window.take_while(class).unwrap()))
+    });
+    group.bench_function(format!("{}/function", name), |b| {
+        b.iter(|| scan(&document, |window| window.take_while(function).unwrap()))
+    });
+    group.bench_function(format!("{}/per_char", name), |b| {
+        b.iter(|| {
+            scan(&document, |window| {
+                while window.peek(function) {
+                    window.take().unwrap();
+                }
+            })
+        })
+    });
+}
+
+/// Scans the whole of `document` with `take`, checking it got to the end.
+fn scan(document: &Document, take: impl Fn(&mut Window)) -> Span {
+    let mut window = Window::new(document);
+    take(&mut window);
+    assert_eq!(window.span().end.offset, document.text.len());
+    window.span()
+}
+
+criterion_group!(benches, lex, token_buffer, take_while);
+criterion_main!(benches);
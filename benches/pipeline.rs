@@ -0,0 +1,37 @@
+//! Parsing and compiling generated sources
+
+mod common;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use spuzc::{bytecode::compiler, parser, text::Document};
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    group.sample_size(10);
+    for (id, text) in common::sources() {
+        let document = Document::new(text.as_str());
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_function(id, |b| b.iter(|| parser::parse(&document).unwrap()));
+    }
+    group.finish();
+}
+
+/// Goes from text to bytecode, including building the document.
+fn compile(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compile");
+    group.sample_size(10);
+    for (id, text) in common::sources() {
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_function(id, |b| {
+            b.iter(|| {
+                let document = Document::new(text.as_str());
+                let module = parser::parse(&document).unwrap();
+                compiler::compile(&module).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse, compile);
+criterion_main!(benches);
@@ -0,0 +1,36 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use spuzc::{
+    text::{Document, Window},
+    tokens::{self, TokenBuffer, TokenError},
+};
+use spuzc_fuzz::{check_span, check_spans};
+
+fuzz_target!(|data: &[u8]| {
+    let document = Document::from_bytes(data);
+
+    let tokens = tokens::tokenize(&document);
+    check_spans(
+        &document,
+        tokens.iter().map(|token| match token {
+            Ok(token) => token.span,
+            Err(error) => error.span,
+        }),
+    );
+
+    let mut buffer = TokenBuffer::new(Window::new(&document));
+    let mut taken = Vec::new();
+    loop {
+        match buffer.take() {
+            Ok(()) => taken.push(buffer.complete().span),
+            Err(TokenError::EndOfFile) => break,
+            Err(_) => {
+                check_span(&document, buffer.pending());
+                break;
+            }
+        }
+    }
+    check_spans(&document, taken);
+    check_spans(&document, buffer.take_comments().iter().map(|c| c.span));
+});
@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use spuzc::{diagnostics::Diagnostic, parser, text::Document, visit::Node};
+use spuzc_fuzz::{check_span, check_tree};
+
+fuzz_target!(|data: &[u8]| {
+    let document = Document::from_bytes(data);
+    let (module, errors) = parser::parse_recovering(&document);
+    check_tree(&document, Node::Module(&module));
+    for error in &errors {
+        if let Some(span) = Diagnostic::from(error).span {
+            check_span(&document, span);
+        }
+    }
+});
@@ -0,0 +1,24 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use spuzc::{formatter, parser, sexpr, text::Document, visit::Node};
+use spuzc_fuzz::{check_tree, Program};
+
+fuzz_target!(|program: Program| {
+    let text = program.to_string();
+    let document = Document::new(text.as_str());
+    let module = match parser::parse(&document) {
+        Ok(module) => module,
+        Err(e) => panic!("generated program doesn't parse: {}\n{}", e, text),
+    };
+    check_tree(&document, Node::Module(&module));
+
+    // Printing keeps the meaning, and printing again changes nothing.
+    let printed = formatter::format(&document).unwrap();
+    let reparsed = parser::parse(&Document::new(printed.as_str())).unwrap();
+    assert_eq!(sexpr::dump(&module, false), sexpr::dump(&reparsed, false));
+    assert_eq!(
+        formatter::format(&Document::new(printed.as_str())).unwrap(),
+        printed
+    );
+});
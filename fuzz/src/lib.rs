@@ -0,0 +1,225 @@
+//! Shared checks and inputs for the fuzz targets
+//!
+//! Run a target with `cargo fuzz run <target>` from the repository root, where the targets are:
+//!
+//! - `lex`, which lexes arbitrary bytes, directly and through a `TokenBuffer`
+//! - `parse`, which parses arbitrary bytes with error recovery
+//! - `round_trip`, which parses programs generated from the grammar and pretty-prints them
+//!
+//! None of them may panic, and every span they produce must lie within the document.
+
+use std::fmt::Write;
+
+use arbitrary::Arbitrary;
+use spuzc::{
+    ast::SyntaxNode,
+    text::{Document, Location, Span},
+    visit::Node,
+};
+
+/// Checks that `span` lies within `document`, starts and ends on character boundaries, and has
+/// locations that agree with the document's line index.
+pub fn check_span(document: &Document, span: Span) {
+    assert!(
+        span.start.offset <= span.end.offset,
+        "span ends before it starts: {:?}",
+        span
+    );
+    check_location(document, span.start);
+    check_location(document, span.end);
+}
+
+fn check_location(document: &Document, location: Location) {
+    assert!(
+        document.text.is_char_boundary(location.offset),
+        "location is out of bounds or inside a character: {:?}",
+        location
+    );
+    assert_eq!(
+        location,
+        document.line_index().location(location.offset),
+        "location disagrees with the line index"
+    );
+}
+
+/// Checks the spans of `spans`, which must come in order without overlapping.
+pub fn check_spans(document: &Document, spans: impl IntoIterator<Item = Span>) {
+    let mut end = 0;
+    for span in spans {
+        check_span(document, span);
+        assert!(
+            span.start.offset >= end,
+            "span overlaps the one before it: {:?}",
+            span
+        );
+        end = span.end.offset;
+    }
+}
+
+/// Checks the span of `node` and, recursively, that its children's spans lie within it.
+pub fn check_tree(document: &Document, node: Node) {
+    let span = node.span();
+    check_span(document, span);
+    for child in node.children() {
+        let inner = child.span();
+        assert!(
+            span.start.offset <= inner.start.offset && inner.end.offset <= span.end.offset,
+            "child {:?} is outside its parent {:?}",
+            inner,
+            span
+        );
+        check_tree(document, child);
+    }
+}
+
+/// A program generated from the grammar, which always parses. It prints as source with
+/// generated whitespace and comments between its tokens.
+#[derive(Debug, Arbitrary)]
+pub struct Program {
+    name: Option<Name>,
+    imports: Vec<(Name, Vec<Name>)>,
+    items: Vec<Func>,
+    trivia: Vec<Trivia>,
+}
+
+#[derive(Debug, Arbitrary)]
+struct Func {
+    public: bool,
+    name: Name,
+    body: Expr,
+}
+
+#[derive(Debug, Arbitrary)]
+enum Expr {
+    Integer(u64),
+    Call(Name),
+}
+
+/// An identifier, picked from names that exercise keywords, raw identifiers and Unicode.
+#[derive(Debug, Arbitrary)]
+struct Name {
+    index: u8,
+    suffix: Option<u16>,
+    raw: bool,
+}
+
+const NAMES: &[&str] = &[
+    // Ordinary names, one of which only matches `é` once normalized.
+    "main", "x", "_", "r", "é", "e\u{301}", "京", "ab_c",
+    // Keywords, which need an `r#` unless they have a suffix.
+    "func", "int", "module", "import", "pub",
+];
+
+const KEYWORDS: &[&str] = &["func", "int", "module", "import", "pub"];
+
+impl std::fmt::Display for Name {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = NAMES[self.index as usize % NAMES.len()];
+        let suffixed = self.suffix.is_some();
+        if self.raw || (KEYWORDS.contains(&name) && !suffixed) {
+            f.write_str("r#")?;
+        }
+        f.write_str(name)?;
+        match self.suffix {
+            Some(suffix) => write!(f, "{}", suffix),
+            None => Ok(()),
+        }
+    }
+}
+
+/// What goes between two tokens.
+#[derive(Debug, Arbitrary)]
+enum Trivia {
+    Space,
+    Tab,
+    LineEnd(LineEnd),
+    Comment(u8, LineEnd),
+}
+
+/// Each of the ways a line can end by default.
+#[derive(Debug, Arbitrary)]
+enum LineEnd {
+    Lf,
+    CrLf,
+    Cr,
+    NextLine,
+    LineSeparator,
+    ParagraphSeparator,
+}
+
+const COMMENTS: &[&str] = &["", " note", " ✨ é", "//", " func main(): int { 1 }"];
+
+impl std::fmt::Display for Trivia {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Trivia::Space => f.write_str(" "),
+            Trivia::Tab => f.write_str("\t"),
+            Trivia::LineEnd(end) => write!(f, "{}", end),
+            Trivia::Comment(i, end) => {
+                write!(f, " //{}{}", COMMENTS[*i as usize % COMMENTS.len()], end)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for LineEnd {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            LineEnd::Lf => "\n",
+            LineEnd::CrLf => "\r\n",
+            LineEnd::Cr => "\r",
+            LineEnd::NextLine => "\u{85}",
+            LineEnd::LineSeparator => "\u{2028}",
+            LineEnd::ParagraphSeparator => "\u{2029}",
+        })
+    }
+}
+
+impl Program {
+    fn tokens(&self) -> Vec<String> {
+        let mut tokens = Vec::new();
+        if let Some(name) = &self.name {
+            tokens.extend(["module".to_owned(), name.to_string(), ";".to_owned()]);
+        }
+        for (first, rest) in &self.imports {
+            tokens.push("import".to_owned());
+            tokens.push(first.to_string());
+            for segment in rest {
+                tokens.push(".".to_owned());
+                tokens.push(segment.to_string());
+            }
+            tokens.push(";".to_owned());
+        }
+        for func in &self.items {
+            if func.public {
+                tokens.push("pub".to_owned());
+            }
+            tokens.push("func".to_owned());
+            tokens.push(func.name.to_string());
+            tokens.extend(["(", ")", ":", "int", "{"].iter().map(|s| s.to_string()));
+            match &func.body {
+                Expr::Integer(i) => tokens.push(i.to_string()),
+                Expr::Call(name) => tokens.extend([name.to_string(), "(".into(), ")".into()]),
+            }
+            tokens.push("}".to_owned());
+        }
+        tokens
+    }
+}
+
+impl std::fmt::Display for Program {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut trivia = self.trivia.iter().cycle();
+        let mut text = String::new();
+        for (i, token) in self.tokens().iter().enumerate() {
+            if i > 0 {
+                match trivia.next() {
+                    Some(trivia) => write!(text, "{}", trivia)?,
+                    None => text.push(' '),
+                }
+            }
+            text.push_str(token);
+        }
+        f.write_str(&text)
+    }
+}
@@ -0,0 +1,436 @@
+//! The AST stored in arenas, for the compiler's passes
+//!
+//! Each kind of node lives in its own `Arena`, and nodes refer to each other by typed `Id`s
+//! instead of holding their children. The arenas double as span tables, holding the span of each
+//! node alongside it, and passes keep what they work out about nodes in `IdMap`s keyed by the same
+//! ids, rather than in the tree itself.
+//!
+//! The parser still builds the boxed tree in `ast`, and the tools that work with source rather
+//! than whole programs use that: the formatter, the visitors in `visit`, the lints, the module
+//! linker, the language server and the REPL. Once a program is linked, `Ast::lower` moves it into
+//! arenas, and the compiler works on ids from there. Converting every tool over to ids isn't worth
+//! it while none of them keeps anything about nodes on the side.
+//!
+//! That leaves two forms of the tree, but the compiler keeps them in step: `lower` matches every
+//! kind of node without a catch-all, and `to_module` builds every field of the boxed nodes, so
+//! neither compiles again until a change to `ast` is made here too.
+
+use std::{
+    convert::TryFrom,
+    marker::PhantomData,
+    ops::{Index, IndexMut},
+};
+
+use super::{Type, Visibility};
+use crate::{
+    symbol::Symbol,
+    text::{Span, Spanned},
+};
+
+/// Identifies a node of type `T` in an `Arena`.
+pub struct Id<T> {
+    index: u32,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Id<T> {
+    fn new(index: usize) -> Id<T> {
+        Id {
+            index: u32::try_from(index).expect("too many nodes"),
+            marker: PhantomData,
+        }
+    }
+
+    /// Gets the position of the node in its arena, in the order the nodes were allocated.
+    pub fn index(self) -> usize {
+        self.index as usize
+    }
+}
+
+// Derives would require `T` to implement each of these too, though an id never holds a `T`.
+impl<T> Clone for Id<T> {
+    fn clone(&self) -> Id<T> {
+        *self
+    }
+}
+
+impl<T> Copy for Id<T> {}
+
+impl<T> PartialEq for Id<T> {
+    fn eq(&self, other: &Id<T>) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<T> Eq for Id<T> {}
+
+impl<T> PartialOrd for Id<T> {
+    fn partial_cmp(&self, other: &Id<T>) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Id<T> {
+    fn cmp(&self, other: &Id<T>) -> std::cmp::Ordering {
+        self.index.cmp(&other.index)
+    }
+}
+
+impl<T> std::hash::Hash for Id<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.index.hash(state)
+    }
+}
+
+impl<T> std::fmt::Debug for Id<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Id({})", self.index)
+    }
+}
+
+/// Nodes of one type, each with its span.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arena<T> {
+    nodes: Vec<T>,
+    spans: Vec<Span>,
+}
+
+impl<T> Arena<T> {
+    pub fn new() -> Arena<T> {
+        Arena {
+            nodes: Vec::new(),
+            spans: Vec::new(),
+        }
+    }
+
+    pub fn alloc(&mut self, node: T, span: Span) -> Id<T> {
+        let id = Id::new(self.nodes.len());
+        self.nodes.push(node);
+        self.spans.push(span);
+        id
+    }
+
+    pub fn span(&self, id: Id<T>) -> Span {
+        self.spans[id.index()]
+    }
+
+    pub fn spanned(&self, id: Id<T>) -> Spanned<T>
+    where
+        T: Clone,
+    {
+        Spanned::new(self[id].clone(), self.span(id))
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Iterates over the nodes in the order they were allocated.
+    pub fn iter(&self) -> impl Iterator<Item = (Id<T>, &T)> {
+        self.nodes.iter().enumerate().map(|(i, n)| (Id::new(i), n))
+    }
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Arena<T> {
+        Arena::new()
+    }
+}
+
+impl<T> Index<Id<T>> for Arena<T> {
+    type Output = T;
+
+    fn index(&self, id: Id<T>) -> &T {
+        &self.nodes[id.index()]
+    }
+}
+
+impl<T> IndexMut<Id<T>> for Arena<T> {
+    fn index_mut(&mut self, id: Id<T>) -> &mut T {
+        &mut self.nodes[id.index()]
+    }
+}
+
+/// A side table holding a value for some of the nodes in an arena.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdMap<T, V> {
+    values: Vec<Option<V>>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T, V> IdMap<T, V> {
+    pub fn new() -> IdMap<T, V> {
+        IdMap {
+            values: Vec::new(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Sets the value for `id`, returning the one it replaced.
+    pub fn insert(&mut self, id: Id<T>, value: V) -> Option<V> {
+        if self.values.len() <= id.index() {
+            self.values.resize_with(id.index() + 1, || None);
+        }
+        self.values[id.index()].replace(value)
+    }
+
+    pub fn get(&self, id: Id<T>) -> Option<&V> {
+        self.values.get(id.index()).and_then(Option::as_ref)
+    }
+
+    pub fn get_mut(&mut self, id: Id<T>) -> Option<&mut V> {
+        self.values.get_mut(id.index()).and_then(Option::as_mut)
+    }
+
+    pub fn contains(&self, id: Id<T>) -> bool {
+        self.get(id).is_some()
+    }
+}
+
+impl<T, V> Default for IdMap<T, V> {
+    fn default() -> IdMap<T, V> {
+        IdMap::new()
+    }
+}
+
+impl<T, V> Index<Id<T>> for IdMap<T, V> {
+    type Output = V;
+
+    fn index(&self, id: Id<T>) -> &V {
+        self.get(id).expect("no value for node")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Module {
+    pub name: Option<Id<Symbol>>,
+    pub imports: Vec<Id<Import>>,
+    pub items: Vec<Id<Item>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Import {
+    pub path: Vec<Id<Symbol>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    Func(FuncDecl),
+    /// Something that failed to parse, which the parser reported an error for.
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuncDecl {
+    pub visibility: Visibility,
+    pub name: Id<Symbol>,
+    pub return_type: Id<Type>,
+    pub body: Id<Block>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub expr: Id<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Integer(i128),
+    Call(Id<Symbol>),
+    /// An expression that failed to parse, which the parser reported an error for.
+    Error,
+}
+
+/// A module and the arenas holding its nodes.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Ast {
+    pub module: Module,
+    /// Every name in the module: its own, import segments, function names and callees.
+    pub names: Arena<Symbol>,
+    pub types: Arena<Type>,
+    pub imports: Arena<Import>,
+    pub items: Arena<Item>,
+    pub blocks: Arena<Block>,
+    pub exprs: Arena<Expr>,
+}
+
+impl Ast {
+    /// Moves `module` into arenas. Nodes are allocated in the order they appear in the source.
+    pub fn lower(module: &super::Module) -> Ast {
+        let mut ast = Ast::default();
+        ast.module.name = module.name.as_ref().map(|name| ast.name(name));
+        for import in &module.imports {
+            let path = import.value.path.iter().map(|s| ast.name(s)).collect();
+            let id = ast.imports.alloc(Import { path }, import.span);
+            ast.module.imports.push(id);
+        }
+        for item in &module.items {
+            let id = ast.item(item);
+            ast.module.items.push(id);
+        }
+        ast
+    }
+
+    /// Rebuilds the tree `lower` was given.
+    pub fn to_module(&self) -> super::Module {
+        super::Module {
+            name: self.module.name.map(|name| self.names.spanned(name)),
+            imports: self
+                .module
+                .imports
+                .iter()
+                .map(|&id| {
+                    let path = self.imports[id]
+                        .path
+                        .iter()
+                        .map(|&s| self.names.spanned(s))
+                        .collect();
+                    Spanned::new(super::Import { path }, self.imports.span(id))
+                })
+                .collect(),
+            items: self
+                .module
+                .items
+                .iter()
+                .map(|&id| self.raise_item(id))
+                .collect(),
+        }
+    }
+
+    /// Gets the functions declared in the module, leaving out anything that failed to parse.
+    pub fn functions(&self) -> impl Iterator<Item = (Id<Item>, &FuncDecl)> + '_ {
+        self.module
+            .items
+            .iter()
+            .filter_map(move |&id| match &self.items[id] {
+                Item::Func(decl) => Some((id, decl)),
+                Item::Error => None,
+            })
+    }
+
+    fn name(&mut self, name: &Spanned<Symbol>) -> Id<Symbol> {
+        self.names.alloc(name.value, name.span)
+    }
+
+    fn item(&mut self, item: &Spanned<super::Item>) -> Id<Item> {
+        let node = match &item.value {
+            super::Item::Func(decl) => {
+                let name = self.name(&decl.name);
+                let return_type = self
+                    .types
+                    .alloc(decl.return_type.value, decl.return_type.span);
+                let expr = self.expr(&decl.body.value.expr);
+                let body = self.blocks.alloc(Block { expr }, decl.body.span);
+                Item::Func(FuncDecl {
+                    visibility: decl.visibility,
+                    name,
+                    return_type,
+                    body,
+                })
+            }
+            super::Item::Error => Item::Error,
+        };
+        self.items.alloc(node, item.span)
+    }
+
+    fn expr(&mut self, expr: &Spanned<super::Expr>) -> Id<Expr> {
+        let node = match &expr.value {
+            super::Expr::Integer(i) => Expr::Integer(*i),
+            super::Expr::Call(name) => Expr::Call(self.name(name)),
+            super::Expr::Error => Expr::Error,
+        };
+        self.exprs.alloc(node, expr.span)
+    }
+
+    fn raise_item(&self, id: Id<Item>) -> Spanned<super::Item> {
+        let item = match &self.items[id] {
+            Item::Func(decl) => {
+                let body = &self.blocks[decl.body];
+                super::Item::Func(super::FuncDecl {
+                    visibility: decl.visibility,
+                    name: self.names.spanned(decl.name),
+                    return_type: self.types.spanned(decl.return_type),
+                    body: Spanned::new(
+                        super::Block {
+                            expr: self.raise_expr(body.expr),
+                        },
+                        self.blocks.span(decl.body),
+                    ),
+                })
+            }
+            Item::Error => super::Item::Error,
+        };
+        Spanned::new(item, self.items.span(id))
+    }
+
+    fn raise_expr(&self, id: Id<Expr>) -> Spanned<super::Expr> {
+        let expr = match &self.exprs[id] {
+            Expr::Integer(i) => super::Expr::Integer(*i),
+            Expr::Call(name) => super::Expr::Call(self.names.spanned(*name)),
+            Expr::Error => super::Expr::Error,
+        };
+        Spanned::new(expr, self.exprs.span(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser, text::Document};
+
+    const SOURCE: &str = "module m;\nimport a.b;\nfunc f(): int { g() }\npub func g(): int { 1 }";
+
+    #[test]
+    pub fn lower() {
+        let module = parser::parse(&Document::new(SOURCE)).unwrap();
+        let ast = Ast::lower(&module);
+
+        assert_eq!(ast.names.len(), 6);
+        assert_eq!(ast.imports[ast.module.imports[0]].path.len(), 2);
+        assert_eq!(ast.items.len(), 2);
+        assert_eq!(ast.exprs.len(), 2);
+
+        let (f, decl) = ast.functions().next().unwrap();
+        assert_eq!(ast.items.span(f), module.items[0].span);
+        assert_eq!(ast.names[decl.name], "f");
+        let expr = ast.blocks[decl.body].expr;
+        match ast.exprs[expr] {
+            Expr::Call(callee) => {
+                assert_eq!(ast.names.spanned(callee).value, "g");
+                assert_eq!(ast.names.span(callee).start, (38, 2, 16).into());
+            }
+            ref e => panic!("expected a call, found {:?}", e),
+        }
+
+        assert_eq!(ast.to_module(), module);
+    }
+
+    #[test]
+    pub fn lower_errors() {
+        let (module, _) = parser::parse_recovering(&Document::new("7 func f(): int { }"));
+        let ast = Ast::lower(&module);
+        assert_eq!(ast.items[ast.module.items[0]], Item::Error);
+        assert_eq!(ast.exprs[Id::new(0)], Expr::Error);
+        assert_eq!(ast.to_module(), module);
+    }
+
+    #[test]
+    pub fn id_map() {
+        let mut arena = Arena::new();
+        let a = arena.alloc("a", Span::ZERO);
+        let b = arena.alloc("b", Span::ZERO);
+        assert!(a < b);
+
+        let mut lengths = IdMap::new();
+        assert_eq!(lengths.get(b), None);
+        assert_eq!(lengths.insert(b, arena[b].len()), None);
+        assert_eq!(lengths.insert(b, 2), Some(1));
+        assert!(!lengths.contains(a));
+        *lengths.get_mut(b).unwrap() += 1;
+        assert_eq!(lengths[b], 3);
+    }
+}
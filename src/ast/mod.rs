@@ -0,0 +1,227 @@
+pub mod arena;
+
+use crate::{
+    symbol::Symbol,
+    text::{Span, Spanned},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Colon,
+    Semicolon,
+    Dot,
+
+    Func,
+    Int,
+    Module,
+    Import,
+    Pub,
+
+    Identifier(Symbol),
+    Integer(i128),
+    /// A `//` comment, holding the text after the slashes up to the end of the line.
+    Comment(String),
+}
+
+impl Token {
+    pub fn kind(&self) -> TokenKind {
+        match self {
+            Token::LParen => TokenKind::LParen,
+            Token::RParen => TokenKind::RParen,
+            Token::LBrace => TokenKind::LBrace,
+            Token::RBrace => TokenKind::RBrace,
+            Token::Colon => TokenKind::Colon,
+            Token::Semicolon => TokenKind::Semicolon,
+            Token::Dot => TokenKind::Dot,
+            Token::Func => TokenKind::Func,
+            Token::Int => TokenKind::Int,
+            Token::Module => TokenKind::Module,
+            Token::Import => TokenKind::Import,
+            Token::Pub => TokenKind::Pub,
+            Token::Identifier(_) => TokenKind::Identifier,
+            Token::Integer(_) => TokenKind::Integer,
+            Token::Comment(_) => TokenKind::Comment,
+        }
+    }
+}
+
+/// Shows the token as it would appear in source.
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Token::LParen => write!(f, "("),
+            Token::RParen => write!(f, ")"),
+            Token::LBrace => write!(f, "{{"),
+            Token::RBrace => write!(f, "}}"),
+            Token::Colon => write!(f, ":"),
+            Token::Semicolon => write!(f, ";"),
+            Token::Dot => write!(f, "."),
+            Token::Func => write!(f, "func"),
+            Token::Int => write!(f, "int"),
+            Token::Module => write!(f, "module"),
+            Token::Import => write!(f, "import"),
+            Token::Pub => write!(f, "pub"),
+            Token::Identifier(name) => write!(f, "{}", Ident(*name)),
+            Token::Integer(i) => write!(f, "{}", i),
+            Token::Comment(text) => write!(f, "//{}", text),
+        }
+    }
+}
+
+/// The sort of a `Token`, without the text or value it carries. Displays the way error messages
+/// refer to tokens, like `'func'` or `identifier`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Colon,
+    Semicolon,
+    Dot,
+
+    Func,
+    Int,
+    Module,
+    Import,
+    Pub,
+
+    Identifier,
+    Integer,
+    Comment,
+}
+
+impl std::fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TokenKind::LParen => write!(f, "'('"),
+            TokenKind::RParen => write!(f, "')'"),
+            TokenKind::LBrace => write!(f, "'{{'"),
+            TokenKind::RBrace => write!(f, "'}}'"),
+            TokenKind::Colon => write!(f, "':'"),
+            TokenKind::Semicolon => write!(f, "';'"),
+            TokenKind::Dot => write!(f, "'.'"),
+            TokenKind::Func => write!(f, "'func'"),
+            TokenKind::Int => write!(f, "'int'"),
+            TokenKind::Module => write!(f, "'module'"),
+            TokenKind::Import => write!(f, "'import'"),
+            TokenKind::Pub => write!(f, "'pub'"),
+            TokenKind::Identifier => write!(f, "identifier"),
+            TokenKind::Integer => write!(f, "integer"),
+            TokenKind::Comment => write!(f, "comment"),
+        }
+    }
+}
+
+/// Shows a name the way it's written in source, as a raw identifier if it's spelled like a
+/// keyword.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ident(pub Symbol);
+
+impl std::fmt::Display for Ident {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.0.is_keyword() {
+            write!(f, "r#{}", self.0)
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+pub trait SyntaxNode {
+    type Children: SyntaxNode;
+    type Iter: Iterator<Item = Self::Children>;
+
+    fn span(&self) -> Span;
+    fn children(&self) -> Self::Iter;
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Module {
+    /// The name given by a `module` declaration, if the module has one.
+    pub name: Option<Spanned<Symbol>>,
+    pub imports: Vec<Spanned<Import>>,
+    pub items: Vec<Spanned<Item>>,
+}
+
+impl Module {
+    /// Gets the functions declared in the module, leaving out anything that failed to parse.
+    pub fn functions(&self) -> impl Iterator<Item = &FuncDecl> + Clone {
+        self.items.iter().filter_map(|item| match &item.value {
+            Item::Func(decl) => Some(decl),
+            Item::Error => None,
+        })
+    }
+}
+
+/// An `import` declaration, naming a module relative to the importing one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Import {
+    pub path: Vec<Spanned<Symbol>>,
+}
+
+impl std::fmt::Display for Import {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, segment) in self.path.iter().enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            write!(f, "{}", Ident(segment.value))?;
+        }
+        Ok(())
+    }
+}
+
+// Nearly every item is a function, so boxing them would only add an indirection.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    Func(FuncDecl),
+    /// Something that failed to parse, which the parser reported an error for.
+    Error,
+}
+
+/// Whether an item can be used from other modules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    Private,
+    Public,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuncDecl {
+    pub visibility: Visibility,
+    pub name: Spanned<Symbol>,
+    pub return_type: Spanned<Type>,
+    pub body: Spanned<Block>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Int,
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub expr: Spanned<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Integer(i128),
+    Call(Spanned<Symbol>),
+    /// An expression that failed to parse, which the parser reported an error for.
+    Error,
+}
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::{
+        arena::{Ast, Block, Expr, FuncDecl, Id, Item},
+        Module,
+    },
+    bytecode::{Function, Instruction, Program},
+    diagnostics::Diagnostic,
+    symbol::Symbol,
+    text::{Span, Spanned},
+};
+
+pub const ENTRY_POINT: Symbol = Symbol::MAIN;
+
+#[derive(Debug, PartialEq)]
+pub enum CompileError {
+    DuplicateFunction {
+        name: Spanned<Symbol>,
+        previous: Span,
+    },
+    UnknownFunction(Spanned<Symbol>),
+    MissingEntryPoint,
+    /// Part of the module failed to parse.
+    Syntax(Span),
+}
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CompileError::DuplicateFunction { name, .. } => {
+                write!(f, "function '{}' is already defined", name.value)
+            }
+            CompileError::UnknownFunction(name) => write!(f, "unknown function '{}'", name.value),
+            CompileError::MissingEntryPoint => write!(f, "no entry point function was defined"),
+            CompileError::Syntax(_) => write!(f, "cannot compile code with syntax errors"),
+        }
+    }
+}
+
+impl From<&CompileError> for Diagnostic {
+    fn from(e: &CompileError) -> Diagnostic {
+        match e {
+            CompileError::DuplicateFunction { name, previous } => {
+                Diagnostic::error(e.to_string(), Some(name.span))
+                    .with_note(*previous, "previously defined here")
+            }
+            CompileError::UnknownFunction(name) => {
+                Diagnostic::error(e.to_string(), Some(name.span))
+            }
+            CompileError::Syntax(span) => Diagnostic::error(e.to_string(), Some(*span)),
+            CompileError::MissingEntryPoint => Diagnostic::error(e.to_string(), None),
+        }
+    }
+}
+
+pub fn compile(module: &Module) -> Result<Program, CompileError> {
+    compile_entry(module, ENTRY_POINT)
+}
+
+/// Compiles `module`, using the function named `entry` as the program's entry point.
+pub fn compile_entry(module: &Module, entry: Symbol) -> Result<Program, CompileError> {
+    let (compiler, functions) = compile_functions(module)?;
+
+    let entry = match compiler.functions.get(&entry) {
+        Some((idx, _)) => *idx,
+        None => return Err(CompileError::MissingEntryPoint),
+    };
+
+    Ok(Program {
+        constants: compiler.constants,
+        functions,
+        entry,
+    })
+}
+
+/// Checks that `module` would compile, without requiring it to have an entry point.
+pub fn check(module: &Module) -> Result<(), CompileError> {
+    compile_functions(module).map(|_| ())
+}
+
+fn compile_functions(module: &Module) -> Result<(Compiler, Vec<Function>), CompileError> {
+    let ast = Ast::lower(module);
+    let mut compiler = Compiler::default();
+
+    // Assign every function an index up front, so calls can refer to functions declared later.
+    let mut decls = Vec::new();
+    for &item in &ast.module.items {
+        let decl = match &ast.items[item] {
+            Item::Func(decl) => decl,
+            Item::Error => return Err(CompileError::Syntax(ast.items.span(item))),
+        };
+        compiler.declare(ast.names.spanned(decl.name))?;
+        decls.push(decl);
+    }
+
+    let mut functions = Vec::with_capacity(decls.len());
+    for decl in decls {
+        functions.push(compiler.function(&ast, decl)?);
+    }
+    Ok((compiler, functions))
+}
+
+#[derive(Default)]
+struct Compiler {
+    constants: Vec<i128>,
+    constant_indices: HashMap<i128, u32>,
+    functions: HashMap<Symbol, (u32, Span)>,
+}
+
+impl Compiler {
+    fn declare(&mut self, name: Spanned<Symbol>) -> Result<(), CompileError> {
+        if let Some((_, previous)) = self.functions.get(&name.value) {
+            return Err(CompileError::DuplicateFunction {
+                name,
+                previous: *previous,
+            });
+        }
+        let idx = self.functions.len() as u32;
+        self.functions.insert(name.value, (idx, name.span));
+        Ok(())
+    }
+
+    fn function(&mut self, ast: &Ast, decl: &FuncDecl) -> Result<Function, CompileError> {
+        let mut function = Function::new(ast.names[decl.name].to_string());
+        self.block(&mut function, ast, decl.body)?;
+        function.emit(Instruction::Return);
+        Ok(function)
+    }
+
+    fn block(
+        &mut self,
+        function: &mut Function,
+        ast: &Ast,
+        block: Id<Block>,
+    ) -> Result<(), CompileError> {
+        self.expr(function, ast, ast.blocks[block].expr)
+    }
+
+    fn expr(
+        &mut self,
+        function: &mut Function,
+        ast: &Ast,
+        expr: Id<Expr>,
+    ) -> Result<(), CompileError> {
+        match ast.exprs[expr] {
+            Expr::Integer(i) => {
+                let idx = self.constant(i);
+                function.emit(Instruction::Const(idx));
+            }
+            Expr::Call(name) => match self.functions.get(&ast.names[name]) {
+                Some((idx, _)) => function.emit(Instruction::Call(*idx)),
+                None => return Err(CompileError::UnknownFunction(ast.names.spanned(name))),
+            },
+            Expr::Error => return Err(CompileError::Syntax(ast.exprs.span(expr))),
+        }
+        Ok(())
+    }
+
+    fn constant(&mut self, value: i128) -> u32 {
+        let constants = &mut self.constants;
+        *self.constant_indices.entry(value).or_insert_with(|| {
+            constants.push(value);
+            (constants.len() - 1) as u32
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser, text::Document};
+
+    fn compile_str(source: &str) -> Result<Program, CompileError> {
+        let doc = Document::new(source);
+        compile(&parser::parse(&doc).unwrap())
+    }
+
+    #[test]
+    pub fn compile_constant() {
+        let program = compile_str("func main(): int { 42 }").unwrap();
+        assert_eq!(program.constants, vec![42]);
+        assert_eq!(program.entry, 0);
+
+        let mut expected = Function::new("main");
+        expected.emit(Instruction::Const(0));
+        expected.emit(Instruction::Return);
+        assert_eq!(program.functions, vec![expected]);
+    }
+
+    #[test]
+    pub fn compile_forward_call() {
+        let program =
+            compile_str("func main(): int { answer() }\nfunc answer(): int { 42 }").unwrap();
+        assert_eq!(program.entry, 0);
+        let code: Vec<_> = program.functions[0]
+            .instructions()
+            .map(|i| i.unwrap().1)
+            .collect();
+        assert_eq!(code, vec![Instruction::Call(1), Instruction::Return]);
+    }
+
+    #[test]
+    pub fn compile_shares_constants() {
+        let program = compile_str("func a(): int { 1 }\nfunc main(): int { 1 }").unwrap();
+        assert_eq!(program.constants, vec![1]);
+        assert_eq!(program.entry, 1);
+    }
+
+    #[test]
+    pub fn compile_unknown_function() {
+        match compile_str("func main(): int { nope() }") {
+            Err(CompileError::UnknownFunction(name)) => assert_eq!(name.value, "nope"),
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    pub fn compile_duplicate_function() {
+        match compile_str("func main(): int { 1 }\nfunc main(): int { 2 }") {
+            Err(CompileError::DuplicateFunction { name, previous }) => {
+                assert_eq!(name.span.start, (28, 1, 5).into());
+                assert_eq!(previous.start, (5, 0, 5).into());
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    pub fn check_without_entry_point() {
+        let doc = Document::new("func answer(): int { 42 }");
+        assert_eq!(check(&parser::parse(&doc).unwrap()), Ok(()));
+    }
+
+    #[test]
+    pub fn compile_missing_entry_point() {
+        assert_eq!(
+            compile_str("func answer(): int { 42 }"),
+            Err(CompileError::MissingEntryPoint)
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt::Write;
+
+use crate::bytecode::{Instruction, Program};
+
+/// Renders a human-readable listing of every function in `program`.
+pub fn disassemble(program: &Program) -> String {
+    let mut out = String::new();
+    write_program(&mut out, program).expect("writing to a String cannot fail");
+    out
+}
+
+fn write_program(out: &mut String, program: &Program) -> std::fmt::Result {
+    writeln!(out, "constants:")?;
+    for (idx, c) in program.constants.iter().enumerate() {
+        writeln!(out, "  #{} = {}", idx, c)?;
+    }
+
+    for (idx, function) in program.functions.iter().enumerate() {
+        writeln!(out)?;
+        write!(out, "func {} (@{})", function.name, idx)?;
+        if idx as u32 == program.entry {
+            write!(out, " [entry]")?;
+        }
+        writeln!(out, ":")?;
+
+        for instruction in function.instructions() {
+            match instruction {
+                Ok((offset, instruction)) => {
+                    write!(out, "  {:04X}  ", offset)?;
+                    write_instruction(out, program, instruction)?;
+                    writeln!(out)?;
+                }
+                Err(e) => writeln!(out, "  <invalid bytecode: {}>", e)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_instruction(
+    out: &mut String,
+    program: &Program,
+    instruction: Instruction,
+) -> std::fmt::Result {
+    match instruction {
+        Instruction::Const(idx) => {
+            write!(out, "const #{}", idx)?;
+            if let Some(c) = program.constants.get(idx as usize) {
+                write!(out, "  ; {}", c)?;
+            }
+        }
+        Instruction::Call(idx) => {
+            write!(out, "call @{}", idx)?;
+            if let Some(f) = program.functions.get(idx as usize) {
+                write!(out, "  ; {}", f.name)?;
+            }
+        }
+        Instruction::Return => write!(out, "return")?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bytecode::{compiler::compile, opcode, Function},
+        parser,
+        text::Document,
+    };
+
+    #[test]
+    pub fn disassemble_program() {
+        let doc = Document::new("func main(): int { answer() }\nfunc answer(): int { 42 }");
+        let program = compile(&parser::parse(&doc).unwrap()).unwrap();
+
+        assert_eq!(
+            disassemble(&program),
+            "constants:\n\
+             \x20 #0 = 42\n\
+             \n\
+             func main (@0) [entry]:\n\
+             \x20 0000  call @1  ; answer\n\
+             \x20 0005  return\n\
+             \n\
+             func answer (@1):\n\
+             \x20 0000  const #0  ; 42\n\
+             \x20 0005  return\n"
+        );
+    }
+
+    #[test]
+    pub fn disassemble_invalid_bytecode() {
+        let mut main = Function::new("main");
+        main.emit(Instruction::Return);
+        main.code.push(0xEE);
+        main.code.push(opcode::RETURN);
+        let program = Program {
+            constants: vec![],
+            functions: vec![main],
+            entry: 0,
+        };
+
+        assert_eq!(
+            disassemble(&program),
+            "constants:\n\
+             \n\
+             func main (@0) [entry]:\n\
+             \x20 0000  return\n\
+             \x20 <invalid bytecode: unknown opcode 0xEE at offset 1>\n"
+        );
+    }
+}
@@ -0,0 +1,166 @@
+//! The `.spzb` file format
+//!
+//! All integers are little-endian.
+//!
+//! ```text
+//! magic       "SPZB"
+//! version     u16
+//! constants   u32 count, then count * i128
+//! functions   u32 count, then count * (u32 name length, UTF-8 name, u32 code length, code)
+//! entry       u32 function index
+//! ```
+
+use std::io::{Read, Write};
+
+use crate::bytecode::{BytecodeError, Function, Program};
+
+pub const MAGIC: &[u8; 4] = b"SPZB";
+pub const VERSION: u16 = 1;
+pub const EXTENSION: &str = "spzb";
+
+impl Program {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+
+        out.extend_from_slice(&(self.constants.len() as u32).to_le_bytes());
+        for c in &self.constants {
+            out.extend_from_slice(&c.to_le_bytes());
+        }
+
+        out.extend_from_slice(&(self.functions.len() as u32).to_le_bytes());
+        for f in &self.functions {
+            out.extend_from_slice(&(f.name.len() as u32).to_le_bytes());
+            out.extend_from_slice(f.name.as_bytes());
+            out.extend_from_slice(&(f.code.len() as u32).to_le_bytes());
+            out.extend_from_slice(&f.code);
+        }
+
+        out.extend_from_slice(&self.entry.to_le_bytes());
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Program, BytecodeError> {
+        let mut reader = Reader { bytes, offset: 0 };
+
+        if reader.bytes(MAGIC.len())? != MAGIC {
+            return Err(BytecodeError::InvalidMagic);
+        }
+        let version = u16::from_le_bytes(reader.array()?);
+        if version != VERSION {
+            return Err(BytecodeError::UnsupportedVersion(version));
+        }
+
+        let count = reader.u32()?;
+        let mut constants = Vec::new();
+        for _ in 0..count {
+            constants.push(i128::from_le_bytes(reader.array()?));
+        }
+
+        let count = reader.u32()?;
+        let mut functions = Vec::new();
+        for _ in 0..count {
+            let len = reader.u32()? as usize;
+            let name = std::str::from_utf8(reader.bytes(len)?)
+                .map_err(|_| BytecodeError::InvalidName)?
+                .to_owned();
+            let len = reader.u32()? as usize;
+            let code = reader.bytes(len)?.to_vec();
+            functions.push(Function { name, code });
+        }
+
+        let entry = reader.u32()?;
+        Ok(Program {
+            constants,
+            functions,
+            entry,
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), BytecodeError> {
+        writer.write_all(&self.to_bytes())?;
+        Ok(())
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> Result<Program, BytecodeError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Program::from_bytes(&bytes)
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], BytecodeError> {
+        let bytes =
+            self.bytes
+                .get(self.offset..self.offset + len)
+                .ok_or(BytecodeError::Truncated {
+                    offset: self.offset,
+                })?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], BytecodeError> {
+        let mut array = [0u8; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    fn u32(&mut self) -> Result<u32, BytecodeError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bytecode::compiler::compile, parser, text::Document};
+
+    fn program() -> Program {
+        let doc = Document::new("func main(): int { answer() }\nfunc answer(): int { 7 }");
+        compile(&parser::parse(&doc).unwrap()).unwrap()
+    }
+
+    #[test]
+    pub fn round_trip() {
+        let program = program();
+        let mut bytes = Vec::new();
+        program.write(&mut bytes).unwrap();
+        assert_eq!(&bytes[0..4], MAGIC);
+        assert_eq!(Program::read(&bytes[..]).unwrap(), program);
+    }
+
+    #[test]
+    pub fn invalid_magic() {
+        match Program::from_bytes(b"SPZX\x01\x00") {
+            Err(BytecodeError::InvalidMagic) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    pub fn unsupported_version() {
+        match Program::from_bytes(b"SPZB\x02\x00") {
+            Err(BytecodeError::UnsupportedVersion(2)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    pub fn truncated() {
+        let bytes = program().to_bytes();
+        for len in 0..bytes.len() {
+            match Program::from_bytes(&bytes[..len]) {
+                Err(BytecodeError::Truncated { .. }) => {}
+                r => panic!("unexpected result for length {}: {:?}", len, r),
+            }
+        }
+    }
+}
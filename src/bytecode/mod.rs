@@ -0,0 +1,228 @@
+//! Bytecode representation for compiled Spuzzum programs
+//!
+//! A [`Program`] is a constant pool and a list of [`Function`]s. Each function's code is a compact
+//! sequence of one-byte opcodes, some followed by little-endian operands. The [`compiler`] turns a
+//! parsed [`Module`](crate::ast::Module) into a program, the [`vm`] executes one, and [`format`]
+//! reads and writes the `.spzb` file format.
+
+pub mod compiler;
+pub mod disasm;
+pub mod format;
+pub mod vm;
+
+pub mod opcode {
+    pub const CONST: u8 = 0x01;
+    pub const CALL: u8 = 0x02;
+    pub const RETURN: u8 = 0x03;
+}
+
+#[derive(Debug)]
+pub enum BytecodeError {
+    UnknownOpcode { opcode: u8, offset: usize },
+    Truncated { offset: usize },
+    InvalidMagic,
+    UnsupportedVersion(u16),
+    InvalidName,
+    Io(std::io::Error),
+}
+
+impl From<std::io::Error> for BytecodeError {
+    fn from(e: std::io::Error) -> BytecodeError {
+        BytecodeError::Io(e)
+    }
+}
+
+impl std::fmt::Display for BytecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BytecodeError::UnknownOpcode { opcode, offset } => {
+                write!(f, "unknown opcode 0x{:02X} at offset {}", opcode, offset)
+            }
+            BytecodeError::Truncated { offset } => {
+                write!(f, "bytecode truncated at offset {}", offset)
+            }
+            BytecodeError::InvalidMagic => write!(f, "not a Spuzzum bytecode file"),
+            BytecodeError::UnsupportedVersion(v) => {
+                write!(f, "unsupported bytecode version {}", v)
+            }
+            BytecodeError::InvalidName => write!(f, "function name is not valid UTF-8"),
+            BytecodeError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// Pushes the constant at the given index in the constant pool.
+    Const(u32),
+    /// Calls the function at the given index, pushing its return value.
+    Call(u32),
+    /// Pops the top of the stack and returns it to the caller.
+    Return,
+}
+
+impl Instruction {
+    pub fn encode(&self, code: &mut Vec<u8>) {
+        match self {
+            Instruction::Const(idx) => {
+                code.push(opcode::CONST);
+                code.extend_from_slice(&idx.to_le_bytes());
+            }
+            Instruction::Call(idx) => {
+                code.push(opcode::CALL);
+                code.extend_from_slice(&idx.to_le_bytes());
+            }
+            Instruction::Return => code.push(opcode::RETURN),
+        }
+    }
+
+    /// Decodes the instruction at `offset`, returning it and the offset of the next instruction.
+    pub fn decode(code: &[u8], offset: usize) -> Result<(Instruction, usize), BytecodeError> {
+        let opcode = *code
+            .get(offset)
+            .ok_or(BytecodeError::Truncated { offset })?;
+        match opcode {
+            opcode::CONST => Ok((Instruction::Const(operand(code, offset + 1)?), offset + 5)),
+            opcode::CALL => Ok((Instruction::Call(operand(code, offset + 1)?), offset + 5)),
+            opcode::RETURN => Ok((Instruction::Return, offset + 1)),
+            opcode => Err(BytecodeError::UnknownOpcode { opcode, offset }),
+        }
+    }
+}
+
+fn operand(code: &[u8], offset: usize) -> Result<u32, BytecodeError> {
+    let mut bytes = [0u8; 4];
+    match code.get(offset..offset + 4) {
+        Some(b) => bytes.copy_from_slice(b),
+        None => return Err(BytecodeError::Truncated { offset }),
+    }
+    Ok(u32::from_le_bytes(bytes))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub name: String,
+    pub code: Vec<u8>,
+}
+
+impl Function {
+    pub fn new<S: Into<String>>(name: S) -> Function {
+        Function {
+            name: name.into(),
+            code: Vec::new(),
+        }
+    }
+
+    pub fn emit(&mut self, instruction: Instruction) {
+        instruction.encode(&mut self.code)
+    }
+
+    pub fn instructions(&self) -> Instructions<'_> {
+        Instructions {
+            code: &self.code,
+            offset: 0,
+        }
+    }
+}
+
+/// Iterates over the decoded instructions of a function, along with their offsets.
+pub struct Instructions<'a> {
+    code: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Result<(usize, Instruction), BytecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.code.len() {
+            return None;
+        }
+
+        match Instruction::decode(self.code, self.offset) {
+            Ok((instruction, next)) => {
+                let offset = std::mem::replace(&mut self.offset, next);
+                Some(Ok((offset, instruction)))
+            }
+            Err(e) => {
+                // Stop iterating, we can't know where the next instruction starts.
+                self.offset = self.code.len();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    pub constants: Vec<i128>,
+    pub functions: Vec<Function>,
+    pub entry: u32,
+}
+
+impl Program {
+    pub fn function(&self, name: &str) -> Option<u32> {
+        self.functions
+            .iter()
+            .position(|f| f.name == name)
+            .map(|i| i as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn instruction_round_trip() {
+        let mut f = Function::new("test");
+        f.emit(Instruction::Const(0x01020304));
+        f.emit(Instruction::Call(7));
+        f.emit(Instruction::Return);
+
+        assert_eq!(
+            f.code,
+            vec![
+                opcode::CONST,
+                0x04,
+                0x03,
+                0x02,
+                0x01,
+                opcode::CALL,
+                7,
+                0,
+                0,
+                0,
+                opcode::RETURN
+            ]
+        );
+        let decoded: Vec<_> = f.instructions().map(|i| i.unwrap()).collect();
+        assert_eq!(
+            decoded,
+            vec![
+                (0, Instruction::Const(0x01020304)),
+                (5, Instruction::Call(7)),
+                (10, Instruction::Return)
+            ]
+        );
+    }
+
+    #[test]
+    pub fn decode_unknown_opcode() {
+        match Instruction::decode(&[opcode::RETURN, 0xFF], 1) {
+            Err(BytecodeError::UnknownOpcode {
+                opcode: 0xFF,
+                offset: 1,
+            }) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    pub fn decode_truncated_operand() {
+        match Instruction::decode(&[opcode::CONST, 0x00, 0x00], 0) {
+            Err(BytecodeError::Truncated { offset: 1 }) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+}
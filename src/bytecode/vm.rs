@@ -0,0 +1,198 @@
+use crate::bytecode::{BytecodeError, Instruction, Program};
+
+/// The maximum number of nested calls before execution is aborted.
+pub const MAX_FRAMES: usize = 1024;
+
+#[derive(Debug)]
+pub enum VmError {
+    Bytecode(BytecodeError),
+    InvalidConstant(u32),
+    InvalidFunction(u32),
+    StackUnderflow,
+    StackOverflow,
+}
+
+impl From<BytecodeError> for VmError {
+    fn from(e: BytecodeError) -> VmError {
+        VmError::Bytecode(e)
+    }
+}
+
+impl std::fmt::Display for VmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            VmError::Bytecode(e) => write!(f, "{}", e),
+            VmError::InvalidConstant(idx) => write!(f, "invalid constant index {}", idx),
+            VmError::InvalidFunction(idx) => write!(f, "invalid function index {}", idx),
+            VmError::StackUnderflow => write!(f, "stack underflow"),
+            VmError::StackOverflow => {
+                write!(f, "stack overflow (more than {} nested calls)", MAX_FRAMES)
+            }
+        }
+    }
+}
+
+struct Frame {
+    function: u32,
+    pc: usize,
+    base: usize,
+}
+
+/// Gets the code a process exits with after running a program that returned `result`. Only the
+/// low 32 bits are kept.
+pub fn exit_code(result: i128) -> i32 {
+    result as i32
+}
+
+pub struct Vm<'a> {
+    program: &'a Program,
+    stack: Vec<i128>,
+    frames: Vec<Frame>,
+}
+
+impl<'a> Vm<'a> {
+    pub fn new(program: &'a Program) -> Vm<'a> {
+        Vm {
+            program,
+            stack: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Runs the program's entry point to completion, returning its result.
+    pub fn run(&mut self) -> Result<i128, VmError> {
+        self.call(self.program.entry)
+    }
+
+    /// Calls the function at index `function` and runs until it returns.
+    pub fn call(&mut self, function: u32) -> Result<i128, VmError> {
+        self.stack.clear();
+        self.frames.clear();
+        self.push_frame(function)?;
+
+        loop {
+            let frame = match self.frames.last_mut() {
+                Some(f) => f,
+                None => return Err(VmError::StackUnderflow),
+            };
+            let code = &self.program.functions[frame.function as usize].code;
+            let (instruction, next) = Instruction::decode(code, frame.pc)?;
+            frame.pc = next;
+
+            match instruction {
+                Instruction::Const(idx) => match self.program.constants.get(idx as usize) {
+                    Some(c) => self.stack.push(*c),
+                    None => return Err(VmError::InvalidConstant(idx)),
+                },
+                Instruction::Call(idx) => self.push_frame(idx)?,
+                Instruction::Return => {
+                    let value = self.stack.pop().ok_or(VmError::StackUnderflow)?;
+                    let frame = self.frames.pop().ok_or(VmError::StackUnderflow)?;
+                    if frame.base > self.stack.len() {
+                        return Err(VmError::StackUnderflow);
+                    }
+                    self.stack.truncate(frame.base);
+
+                    if self.frames.is_empty() {
+                        return Ok(value);
+                    }
+                    self.stack.push(value);
+                }
+            }
+        }
+    }
+
+    fn push_frame(&mut self, function: u32) -> Result<(), VmError> {
+        if function as usize >= self.program.functions.len() {
+            return Err(VmError::InvalidFunction(function));
+        }
+        if self.frames.len() >= MAX_FRAMES {
+            return Err(VmError::StackOverflow);
+        }
+        self.frames.push(Frame {
+            function,
+            pc: 0,
+            base: self.stack.len(),
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bytecode::{compiler::compile, Function},
+        parser,
+        text::Document,
+    };
+
+    fn run_str(source: &str) -> Result<i128, VmError> {
+        let doc = Document::new(source);
+        let program = compile(&parser::parse(&doc).unwrap()).unwrap();
+        Vm::new(&program).run()
+    }
+
+    #[test]
+    pub fn run_constant() {
+        assert_eq!(run_str("func main(): int { 42 }").unwrap(), 42);
+    }
+
+    #[test]
+    pub fn run_nested_calls() {
+        let source = "func main(): int { a() }\nfunc a(): int { b() }\nfunc b(): int { 7 }";
+        assert_eq!(run_str(source).unwrap(), 7);
+    }
+
+    #[test]
+    pub fn run_infinite_recursion() {
+        match run_str("func main(): int { main() }") {
+            Err(VmError::StackOverflow) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    pub fn run_invalid_constant() {
+        let mut main = Function::new("main");
+        main.emit(Instruction::Const(3));
+        main.emit(Instruction::Return);
+        let program = Program {
+            constants: vec![],
+            functions: vec![main],
+            entry: 0,
+        };
+        match Vm::new(&program).run() {
+            Err(VmError::InvalidConstant(3)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    pub fn run_return_empty_stack() {
+        let mut main = Function::new("main");
+        main.emit(Instruction::Return);
+        let program = Program {
+            constants: vec![],
+            functions: vec![main],
+            entry: 0,
+        };
+        match Vm::new(&program).run() {
+            Err(VmError::StackUnderflow) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    pub fn run_off_end_of_code() {
+        let program = Program {
+            constants: vec![],
+            functions: vec![Function::new("main")],
+            entry: 0,
+        };
+        match Vm::new(&program).run() {
+            Err(VmError::Bytecode(BytecodeError::Truncated { offset: 0 })) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+}
@@ -0,0 +1,263 @@
+//! Lexing and parsing text into a green tree, keeping everything
+
+use std::sync::Arc;
+
+use super::{
+    green::{GreenBuilder, GreenNode},
+    SyntaxKind,
+};
+use crate::{
+    ast::Token,
+    text::{CharClass, Document, Window},
+    tokens::{rules, TokenError},
+};
+
+pub(super) fn build(document: &Document) -> Arc<GreenNode> {
+    let mut parser = Parser {
+        tokens: lex(document),
+        position: 0,
+        builder: GreenBuilder::new(),
+    };
+    parser.module();
+    parser.builder.finish()
+}
+
+/// Parses `document` as a single block, if it's exactly one block from its `{` to its closing
+/// `}`.
+pub(super) fn reparse_block(document: &Document) -> Option<Arc<GreenNode>> {
+    let tokens = lex(document);
+    let kinds = (tokens.first().map(|t| t.0), tokens.last().map(|t| t.0));
+    if kinds != (Some(SyntaxKind::LBrace), Some(SyntaxKind::RBrace)) {
+        return None;
+    }
+
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        builder: GreenBuilder::new(),
+    };
+    parser.block();
+    // Nothing in a block takes a `}` but the one closing it, so if everything was used, the
+    // last `}` closed the block.
+    if parser.position < parser.tokens.len() {
+        return None;
+    }
+    Some(parser.builder.finish())
+}
+
+/// Splits `document` into tokens, including whitespace, comments and anything that isn't valid.
+pub(super) fn lex(document: &Document) -> Vec<(SyntaxKind, &str)> {
+    let mut window = Window::new(document);
+    let mut tokens = Vec::new();
+    loop {
+        if window.take_while(CharClass::WHITESPACE).is_err() {
+            break;
+        }
+        if !window.content().is_empty() {
+            let span = window.advance();
+            tokens.push((SyntaxKind::Whitespace, document.text_at(span)));
+        }
+
+        match rules::next_token(&mut window) {
+            Ok(token) => tokens.push((kind_of(&token.value), document.text_at(token.span))),
+            Err(TokenError::EndOfFile) => break,
+            Err(_) => {
+                // The window still covers whatever the lexer gave up on.
+                let span = window.advance();
+                if span.start == span.end {
+                    break;
+                }
+                tokens.push((SyntaxKind::Unknown, document.text_at(span)));
+            }
+        }
+    }
+    tokens
+}
+
+fn kind_of(token: &Token) -> SyntaxKind {
+    match token {
+        Token::LParen => SyntaxKind::LParen,
+        Token::RParen => SyntaxKind::RParen,
+        Token::LBrace => SyntaxKind::LBrace,
+        Token::RBrace => SyntaxKind::RBrace,
+        Token::Colon => SyntaxKind::Colon,
+        Token::Semicolon => SyntaxKind::Semicolon,
+        Token::Dot => SyntaxKind::Dot,
+        Token::Func => SyntaxKind::FuncKw,
+        Token::Int => SyntaxKind::IntKw,
+        Token::Module => SyntaxKind::ModuleKw,
+        Token::Import => SyntaxKind::ImportKw,
+        Token::Pub => SyntaxKind::PubKw,
+        Token::Identifier(_) => SyntaxKind::Identifier,
+        Token::Integer(_) => SyntaxKind::Integer,
+        Token::Comment(_) => SyntaxKind::Comment,
+    }
+}
+
+/// Builds the tree without ever failing. Missing tokens are left out, and tokens that don't fit
+/// where they are go into `Error` nodes.
+struct Parser<'a> {
+    tokens: Vec<(SyntaxKind, &'a str)>,
+    position: usize,
+    builder: GreenBuilder,
+}
+
+impl Parser<'_> {
+    fn module(&mut self) {
+        self.builder.start_node(SyntaxKind::Module);
+        while let Some(kind) = self.peek() {
+            match kind {
+                SyntaxKind::ModuleKw => self.module_decl(),
+                SyntaxKind::ImportKw => self.import(),
+                SyntaxKind::FuncKw | SyntaxKind::PubKw => self.func_decl(),
+                _ => {
+                    self.start_node(SyntaxKind::Error);
+                    self.bump();
+                    self.builder.finish_node();
+                }
+            }
+        }
+        self.eat_trivia();
+        self.builder.finish_node();
+    }
+
+    fn module_decl(&mut self) {
+        self.start_node(SyntaxKind::ModuleDecl);
+        self.bump();
+        self.expect(SyntaxKind::Identifier);
+        self.expect(SyntaxKind::Semicolon);
+        self.builder.finish_node();
+    }
+
+    fn import(&mut self) {
+        self.start_node(SyntaxKind::Import);
+        self.bump();
+        self.start_node(SyntaxKind::Path);
+        self.expect(SyntaxKind::Identifier);
+        while self.at(SyntaxKind::Dot) {
+            self.bump();
+            self.expect(SyntaxKind::Identifier);
+        }
+        self.builder.finish_node();
+        self.expect(SyntaxKind::Semicolon);
+        self.builder.finish_node();
+    }
+
+    fn func_decl(&mut self) {
+        self.start_node(SyntaxKind::FuncDecl);
+        self.expect(SyntaxKind::PubKw);
+        self.expect(SyntaxKind::FuncKw);
+        self.expect(SyntaxKind::Identifier);
+        if self.at(SyntaxKind::LParen) {
+            self.start_node(SyntaxKind::ParamList);
+            self.bump();
+            self.expect(SyntaxKind::RParen);
+            self.builder.finish_node();
+        }
+        self.expect(SyntaxKind::Colon);
+        if self.at(SyntaxKind::IntKw) {
+            self.start_node(SyntaxKind::TypeRef);
+            self.bump();
+            self.builder.finish_node();
+        }
+        if self.at(SyntaxKind::LBrace) {
+            self.block();
+        }
+        self.builder.finish_node();
+    }
+
+    fn block(&mut self) {
+        self.start_node(SyntaxKind::Block);
+        self.bump();
+        self.expr();
+
+        // Skip to the end of the block, or the next item if it doesn't seem to have one.
+        if !self.at(SyntaxKind::RBrace) && !self.at_item() && self.peek().is_some() {
+            self.start_node(SyntaxKind::Error);
+            while !self.at(SyntaxKind::RBrace) && !self.at_item() && self.peek().is_some() {
+                self.bump();
+            }
+            self.builder.finish_node();
+        }
+        self.expect(SyntaxKind::RBrace);
+        self.builder.finish_node();
+    }
+
+    fn expr(&mut self) {
+        match self.peek() {
+            Some(SyntaxKind::Integer) => {
+                self.start_node(SyntaxKind::IntegerExpr);
+                self.bump();
+                self.builder.finish_node();
+            }
+            Some(SyntaxKind::Identifier) => {
+                self.start_node(SyntaxKind::CallExpr);
+                self.bump();
+                if self.at(SyntaxKind::LParen) {
+                    self.start_node(SyntaxKind::ArgList);
+                    self.bump();
+                    self.expect(SyntaxKind::RParen);
+                    self.builder.finish_node();
+                }
+                self.builder.finish_node();
+            }
+            _ => {}
+        }
+    }
+
+    /// Gets the kind of the next token that isn't trivia.
+    fn peek(&self) -> Option<SyntaxKind> {
+        self.tokens[self.position..]
+            .iter()
+            .map(|&(kind, _)| kind)
+            .find(|kind| !kind.is_trivia())
+    }
+
+    fn at(&self, kind: SyntaxKind) -> bool {
+        self.peek() == Some(kind)
+    }
+
+    fn at_item(&self) -> bool {
+        matches!(
+            self.peek(),
+            Some(SyntaxKind::FuncKw)
+                | Some(SyntaxKind::PubKw)
+                | Some(SyntaxKind::ModuleKw)
+                | Some(SyntaxKind::ImportKw)
+        )
+    }
+
+    /// Takes the next token if it's `kind`.
+    fn expect(&mut self, kind: SyntaxKind) -> bool {
+        let found = self.at(kind);
+        if found {
+            self.bump();
+        }
+        found
+    }
+
+    /// Adds the next token to the current node, along with any trivia before it.
+    fn bump(&mut self) {
+        self.eat_trivia();
+        if let Some(&(kind, text)) = self.tokens.get(self.position) {
+            self.builder.token(kind, text);
+            self.position += 1;
+        }
+    }
+
+    fn eat_trivia(&mut self) {
+        while let Some(&(kind, text)) = self.tokens.get(self.position) {
+            if !kind.is_trivia() {
+                break;
+            }
+            self.builder.token(kind, text);
+            self.position += 1;
+        }
+    }
+
+    /// Starts a node, leaving the trivia before it in the parent so nodes start with a token.
+    fn start_node(&mut self, kind: SyntaxKind) {
+        self.eat_trivia();
+        self.builder.start_node(kind);
+    }
+}
@@ -0,0 +1,212 @@
+//! The green tree: immutable nodes that only know their kind, text and length
+//!
+//! Green nodes have no idea where they are, so identical subtrees can be shared between trees,
+//! and between versions of the same tree.
+
+use std::sync::Arc;
+
+use super::SyntaxKind;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GreenToken {
+    kind: SyntaxKind,
+    text: String,
+}
+
+impl GreenToken {
+    pub fn new<S: Into<String>>(kind: SyntaxKind, text: S) -> GreenToken {
+        GreenToken {
+            kind,
+            text: text.into(),
+        }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn len(&self) -> usize {
+        self.text.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GreenElement {
+    Node(Arc<GreenNode>),
+    Token(Arc<GreenToken>),
+}
+
+impl GreenElement {
+    pub fn kind(&self) -> SyntaxKind {
+        match self {
+            GreenElement::Node(n) => n.kind(),
+            GreenElement::Token(t) => t.kind(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            GreenElement::Node(n) => n.len(),
+            GreenElement::Token(t) => t.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GreenNode {
+    kind: SyntaxKind,
+    len: usize,
+    children: Vec<GreenElement>,
+}
+
+impl GreenNode {
+    pub fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> GreenNode {
+        GreenNode {
+            kind,
+            len: children.iter().map(GreenElement::len).sum(),
+            children,
+        }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    /// Gets the length of the node's text in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+
+    /// Makes a copy of the node with one child replaced, sharing all the others.
+    pub fn replace_child(&self, index: usize, child: GreenElement) -> GreenNode {
+        let mut children = self.children.clone();
+        children[index] = child;
+        GreenNode::new(self.kind, children)
+    }
+}
+
+impl std::fmt::Display for GreenNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for child in &self.children {
+            match child {
+                GreenElement::Node(n) => write!(f, "{}", n)?,
+                GreenElement::Token(t) => write!(f, "{}", t.text)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A point in a `GreenBuilder` a node can later be started from, to wrap what was built since.
+#[derive(Copy, Clone, Debug)]
+pub struct Checkpoint(usize);
+
+/// Builds a green tree from the top down.
+#[derive(Default)]
+pub struct GreenBuilder {
+    /// The nodes started but not finished, with where their children begin in `children`.
+    parents: Vec<(SyntaxKind, usize)>,
+    children: Vec<GreenElement>,
+}
+
+impl GreenBuilder {
+    pub fn new() -> GreenBuilder {
+        GreenBuilder::default()
+    }
+
+    pub fn start_node(&mut self, kind: SyntaxKind) {
+        self.parents.push((kind, self.children.len()));
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.children.len())
+    }
+
+    /// Starts a node containing everything added since `checkpoint`.
+    pub fn start_node_at(&mut self, checkpoint: Checkpoint, kind: SyntaxKind) {
+        assert!(
+            checkpoint.0 <= self.children.len(),
+            "checkpoint is no longer valid"
+        );
+        self.parents.push((kind, checkpoint.0));
+    }
+
+    pub fn token(&mut self, kind: SyntaxKind, text: &str) {
+        self.children
+            .push(GreenElement::Token(Arc::new(GreenToken::new(kind, text))));
+    }
+
+    /// Adds an existing subtree as a child of the current node.
+    pub fn node(&mut self, node: Arc<GreenNode>) {
+        self.children.push(GreenElement::Node(node));
+    }
+
+    pub fn finish_node(&mut self) {
+        let (kind, start) = self.parents.pop().expect("no node to finish");
+        let children = self.children.split_off(start);
+        self.node(Arc::new(GreenNode::new(kind, children)));
+    }
+
+    /// Returns the root, which must be the only node left.
+    pub fn finish(mut self) -> Arc<GreenNode> {
+        assert!(self.parents.is_empty(), "unfinished nodes");
+        match self.children.pop() {
+            Some(GreenElement::Node(root)) if self.children.is_empty() => root,
+            _ => panic!("a tree must have exactly one root node"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn build() {
+        let mut builder = GreenBuilder::new();
+        builder.start_node(SyntaxKind::Block);
+        builder.token(SyntaxKind::LBrace, "{");
+        builder.token(SyntaxKind::Whitespace, " ");
+        let checkpoint = builder.checkpoint();
+        builder.token(SyntaxKind::Integer, "42");
+        builder.start_node_at(checkpoint, SyntaxKind::IntegerExpr);
+        builder.finish_node();
+        builder.token(SyntaxKind::RBrace, "}");
+        builder.finish_node();
+        let root = builder.finish();
+
+        assert_eq!(root.kind(), SyntaxKind::Block);
+        assert_eq!(root.len(), 5);
+        assert_eq!(root.to_string(), "{ 42}");
+        let kinds: Vec<_> = root.children().iter().map(GreenElement::kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                SyntaxKind::LBrace,
+                SyntaxKind::Whitespace,
+                SyntaxKind::IntegerExpr,
+                SyntaxKind::RBrace
+            ]
+        );
+    }
+}
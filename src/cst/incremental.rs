@@ -0,0 +1,277 @@
+//! Reparsing only the part of a tree an edit touches
+//!
+//! An edit inside one token that still lexes as a single token of the same kind can't change the
+//! structure of the tree, so only that token is replaced. Failing that, an edit between the braces
+//! of a block is reparsed as just that block, as long as the block still ends at its old `}`.
+//! Anything else needs a full reparse. Whatever isn't replaced is shared with the old tree.
+//!
+//! The text that is lexed again is lexed as a fragment of the edited document, so it follows the
+//! document's line endings and other settings just as a full reparse would.
+
+use std::{ops::Range, sync::Arc};
+
+use super::{
+    builder,
+    green::{GreenElement, GreenNode, GreenToken},
+    SyntaxKind,
+};
+use crate::text::{Document, TextEdit};
+
+/// The nodes from the root down to an element, each with the index of the next one.
+type Path = Vec<(Arc<GreenNode>, usize)>;
+
+/// Applies `edit` to the tree, or returns `None` if the document has to be parsed again.
+/// `document` is the edited document.
+pub(super) fn reparse(
+    root: &Arc<GreenNode>,
+    edit: &TextEdit,
+    document: &Document,
+) -> Option<Arc<GreenNode>> {
+    relex_token(root, edit, document).or_else(|| reparse_block(root, edit, document))
+}
+
+fn relex_token(
+    root: &Arc<GreenNode>,
+    edit: &TextEdit,
+    document: &Document,
+) -> Option<Arc<GreenNode>> {
+    let (path, element, start) = find(root, &edit.range, |_| false)?;
+    let token = match element {
+        GreenElement::Token(t) if t.kind() != SyntaxKind::Unknown => t,
+        _ => return None,
+    };
+    let new = GreenToken::new(token.kind(), edit.apply_at(token.text(), start));
+
+    // The tokens either side have to lex the same too, or the edit may have joined them to this
+    // one, like a comment running into the whitespace after it.
+    let before = start
+        .checked_sub(1)
+        .and_then(|offset| token_at(root, offset));
+    let after = token_at(root, start + token.len());
+    let expected: Vec<&GreenToken> = before.into_iter().chain(Some(&new)).chain(after).collect();
+    let text: String = expected.iter().map(|t| t.text()).collect();
+    let fragment = document.fragment(text);
+    let lexed = builder::lex(&fragment);
+    let same = lexed.len() == expected.len()
+        && lexed
+            .iter()
+            .zip(&expected)
+            .all(|(&(kind, text), t)| kind == t.kind() && text == t.text());
+    if !same {
+        return None;
+    }
+    Some(rebuild(path, GreenElement::Token(Arc::new(new))))
+}
+
+fn reparse_block(
+    root: &Arc<GreenNode>,
+    edit: &TextEdit,
+    document: &Document,
+) -> Option<Arc<GreenNode>> {
+    let (path, element, start) = find(root, &edit.range, |e| e.kind() == SyntaxKind::Block)?;
+    let block = match element {
+        GreenElement::Node(n) if n.kind() == SyntaxKind::Block => n,
+        _ => return None,
+    };
+    let closed = matches!(
+        block.children().last(),
+        Some(GreenElement::Token(t)) if t.kind() == SyntaxKind::RBrace
+    );
+    if !closed || edit.range.start <= start || edit.range.end >= start + block.len() {
+        return None;
+    }
+
+    let text = edit.apply_at(&block.to_string(), start);
+    let block = builder::reparse_block(&document.fragment(text))?;
+    Some(rebuild(path, GreenElement::Node(block)))
+}
+
+/// Walks down to the first element containing `range` that is a token or that `stop` accepts,
+/// returning the path to it and its offset.
+fn find(
+    root: &Arc<GreenNode>,
+    range: &Range<usize>,
+    stop: fn(&GreenElement) -> bool,
+) -> Option<(Path, GreenElement, usize)> {
+    let mut path = Vec::new();
+    let mut node = root.clone();
+    let mut start = 0;
+    loop {
+        let (index, child) = node
+            .children()
+            .iter()
+            .enumerate()
+            .find(|(_, child)| {
+                let end = start + child.len();
+                let found = start <= range.start && range.end <= end;
+                if !found {
+                    start = end;
+                }
+                found
+            })
+            .map(|(index, child)| (index, child.clone()))?;
+        path.push((node, index));
+        match child {
+            GreenElement::Node(n) if !stop(&child) => node = n,
+            _ => return Some((path, child, start)),
+        }
+    }
+}
+
+/// Finds the token containing the byte at `offset`.
+fn token_at(root: &GreenNode, offset: usize) -> Option<&GreenToken> {
+    let mut node = root;
+    let mut start = 0;
+    'descend: loop {
+        for child in node.children() {
+            let end = start + child.len();
+            if offset < end {
+                match child {
+                    GreenElement::Node(n) => {
+                        node = n;
+                        continue 'descend;
+                    }
+                    GreenElement::Token(t) => return Some(t),
+                }
+            }
+            start = end;
+        }
+        return None;
+    }
+}
+
+/// Replaces the element at the end of `path`, copying the nodes above it.
+fn rebuild(path: Path, replacement: GreenElement) -> Arc<GreenNode> {
+    let mut element = replacement;
+    for (node, index) in path.into_iter().rev() {
+        element = GreenElement::Node(Arc::new(node.replace_child(index, element)));
+    }
+    match element {
+        GreenElement::Node(root) => root,
+        GreenElement::Token(_) => unreachable!("the root is always a node"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::LineEndings;
+    use proptest::prelude::*;
+
+    /// Applies `edit` both ways, checking the incremental result matches a full reparse.
+    fn check(text: &str, edit: TextEdit) -> (Arc<GreenNode>, Option<Arc<GreenNode>>) {
+        check_with(text, LineEndings::default(), edit)
+    }
+
+    fn check_with(
+        text: &str,
+        line_endings: LineEndings,
+        edit: TextEdit,
+    ) -> (Arc<GreenNode>, Option<Arc<GreenNode>>) {
+        let mut document = Document::with_line_endings(text, line_endings);
+        let old = builder::build(&document);
+        document.apply(&edit);
+        let full = builder::build(&document);
+        let incremental = reparse(&old, &edit, &document);
+        if let Some(new) = &incremental {
+            assert_eq!(new, &full, "editing {:?} with {:?}", text, edit);
+        }
+        (old, incremental)
+    }
+
+    fn functions(root: &GreenNode) -> Vec<&Arc<GreenNode>> {
+        root.children()
+            .iter()
+            .filter_map(|c| match c {
+                GreenElement::Node(n) if n.kind() == SyntaxKind::FuncDecl => Some(n),
+                _ => None,
+            })
+            .collect()
+    }
+
+    const SOURCE: &str = "func a(): int { 1 }\n// b\nfunc b(): int { a() }\n";
+
+    #[test]
+    pub fn relex_token() {
+        let (old, new) = check(SOURCE, TextEdit::new(16..17, "42"));
+        let new = new.expect("the integer should be relexed");
+        assert!(Arc::ptr_eq(functions(&old)[1], functions(&new)[1]));
+
+        assert!(check(SOURCE, TextEdit::new(22..22, "ee")).1.is_some());
+        assert!(check(SOURCE, TextEdit::new(41..42, "abc")).1.is_some());
+    }
+
+    #[test]
+    pub fn reparse_block() {
+        let (old, new) = check(SOURCE, TextEdit::new(41..44, "7"));
+        let new = new.expect("the block should be reparsed");
+        assert_eq!(
+            new.to_string(),
+            "func a(): int { 1 }\n// b\nfunc b(): int { 7 }\n"
+        );
+        assert!(Arc::ptr_eq(functions(&old)[0], functions(&new)[0]));
+
+        assert!(check(SOURCE, TextEdit::new(16..17, "1 2 x")).1.is_some());
+    }
+
+    #[test]
+    pub fn full_reparse() {
+        // The new text joins tokens or changes their kind
+        assert!(check(SOURCE, TextEdit::new(24..25, " ")).1.is_none());
+        assert!(check(SOURCE, TextEdit::new(25..29, "fun")).1.is_none());
+        assert!(check(SOURCE, TextEdit::new(41..42, "func")).1.is_none());
+        // The block no longer ends at its `}`
+        assert!(check(SOURCE, TextEdit::new(16..17, "// 1")).1.is_none());
+        assert!(check(SOURCE, TextEdit::new(16..17, "1 } {")).1.is_none());
+        assert!(check(SOURCE, TextEdit::new(18..19, "")).1.is_none());
+        assert!(check("", TextEdit::new(0..0, "func")).1.is_none());
+    }
+
+    #[test]
+    pub fn line_endings() {
+        // Only '\n' ends a line, so the comment runs on to the end of the text.
+        let text = "func f(): int { 1 }";
+        let edit = TextEdit::new(16..16, "// a\u{2028} 2 ");
+        assert!(check_with(text, LineEndings::Lf, edit.clone()).1.is_none());
+        assert!(check_with(text, LineEndings::Unicode, edit).1.is_some());
+
+        // NEL is only whitespace here, so the block can still be reparsed on its own.
+        let (_, new) = check_with(text, LineEndings::Ascii, TextEdit::new(16..17, "\u{85}7"));
+        assert!(new.is_some());
+    }
+
+    proptest! {
+        /// Random edits to a few sources, under each line ending policy, reparse to the same tree
+        /// as a full parse whenever they can be reparsed incrementally.
+        #[test]
+        fn random_edits(
+            text in prop::sample::select(vec![
+                SOURCE,
+                "module m;\nimport a.b;\npub func f(): int { g() } // done\n",
+                "func f(): int { 1 2 } } 7 func g(): int {",
+                "func f(): int {\r\n  1 // one\u{2028}}\u{85}// é\rfunc g(): int { 2 }",
+            ]),
+            line_endings in prop::sample::select(vec![
+                LineEndings::Lf,
+                LineEndings::Ascii,
+                LineEndings::Unicode,
+            ]),
+            start in any::<prop::sample::Index>(),
+            len in 0..4usize,
+            fragment in prop::sample::select(vec![
+                "", " ", "\n", "\r", "\u{2028}", "1", "x", "func", "int", "{", "}", "(", ")", ":",
+                "//", "✨",
+            ]),
+        ) {
+            let boundaries: Vec<usize> = text
+                .char_indices()
+                .map(|(offset, _)| offset)
+                .chain(std::iter::once(text.len()))
+                .collect();
+            let start = start.index(boundaries.len());
+            let end = (start + len).min(boundaries.len() - 1);
+            let edit = TextEdit::new(boundaries[start]..boundaries[end], fragment);
+            check_with(text, line_endings, edit);
+        }
+    }
+}
@@ -0,0 +1,274 @@
+//! Lossless concrete syntax trees
+//!
+//! Unlike the AST, a concrete syntax tree keeps every byte of the document, whitespace and
+//! comments included, so printing it gives back exactly the text it was parsed from. That makes
+//! it the right tree for tools that edit source, like the formatter or an IDE. It's also built
+//! for any text at all: whatever doesn't parse ends up in `Error` nodes.
+//!
+//! Trees come in two layers. The green tree in `green` holds the structure and text, and the red
+//! tree in `red` adds positions on top. Typed views in `views` give the nodes of the red tree an
+//! AST-like interface.
+
+mod builder;
+pub mod green;
+mod incremental;
+pub mod red;
+pub mod views;
+
+use std::sync::Arc;
+
+use crate::text::{Document, TextEdit};
+use green::GreenNode;
+use red::RedNode;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum SyntaxKind {
+    // Trivia
+    Whitespace,
+    Comment,
+
+    // Tokens
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Colon,
+    Semicolon,
+    Dot,
+    FuncKw,
+    IntKw,
+    ModuleKw,
+    ImportKw,
+    PubKw,
+    Identifier,
+    Integer,
+    /// Text the lexer couldn't make sense of.
+    Unknown,
+
+    // Nodes
+    Module,
+    ModuleDecl,
+    Import,
+    Path,
+    FuncDecl,
+    ParamList,
+    TypeRef,
+    Block,
+    IntegerExpr,
+    CallExpr,
+    ArgList,
+    Error,
+}
+
+impl SyntaxKind {
+    /// Determines if tokens of this kind only separate other tokens, and don't mean anything.
+    pub fn is_trivia(self) -> bool {
+        matches!(self, SyntaxKind::Whitespace | SyntaxKind::Comment)
+    }
+}
+
+/// A concrete syntax tree for a whole document.
+pub struct SyntaxTree {
+    root: RedNode,
+}
+
+impl SyntaxTree {
+    pub fn parse(document: &Document) -> SyntaxTree {
+        let green = builder::build(document);
+        SyntaxTree::new(green, document)
+    }
+
+    /// Wraps a green tree for `document`, which it must have been built from. The tree shares the
+    /// document's line index, which `Document::apply` keeps up to date without rebuilding it.
+    pub fn new(green: Arc<GreenNode>, document: &Document) -> SyntaxTree {
+        debug_assert_eq!(green.len(), document.text.len());
+        let line_index = document.shared_line_index().clone();
+        SyntaxTree {
+            root: RedNode::new_root(green, document.file(), line_index),
+        }
+    }
+
+    /// Updates the tree for `edit`, reparsing as little as possible. `document` is the document
+    /// the tree was parsed from, with the edit already applied.
+    pub fn reparse(&self, edit: &TextEdit, document: &Document) -> SyntaxTree {
+        let green = incremental::reparse(self.root.green(), edit, document)
+            .unwrap_or_else(|| builder::build(document));
+        SyntaxTree::new(green, document)
+    }
+
+    pub fn root(&self) -> &RedNode {
+        &self.root
+    }
+
+    pub fn module(&self) -> views::Module {
+        views::Module::cast(self.root.clone()).expect("the root is always a module")
+    }
+}
+
+impl std::fmt::Display for SyntaxTree {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::SyntaxNode, parser, text::SourceDatabase};
+
+    fn parse(text: &str) -> SyntaxTree {
+        SyntaxTree::parse(&Document::new(text))
+    }
+
+    /// Describes the tree as nested kinds, leaving out trivia.
+    fn shape(node: &RedNode) -> String {
+        let children: Vec<_> = node
+            .children_with_tokens()
+            .into_iter()
+            .filter(|c| !c.kind().is_trivia())
+            .map(|c| match c {
+                red::RedElement::Node(n) => shape(&n),
+                red::RedElement::Token(t) => format!("{:?}", t.kind()),
+            })
+            .collect();
+        format!("({:?} {})", node.kind(), children.join(" "))
+    }
+
+    #[test]
+    pub fn lossless() {
+        let sources = [
+            "",
+            "   \n\t",
+            "func main(): int {\n    42\n}",
+            "// leading\r\nmodule m; import a . b ;\npub func f ( ) : int { g ( ) } // trailing",
+            "func main(): int { 42",
+            "func main() int } 7 ✨ { ¿ 99999999999999999999999999999999999999999999",
+            "}}}} import ; module",
+        ];
+        for source in sources.iter() {
+            assert_eq!(parse(source).to_string(), *source);
+        }
+    }
+
+    #[test]
+    pub fn structure() {
+        let tree = parse("module m;\nimport a.b;\n// f\npub func f(): int { g() }");
+        assert_eq!(
+            shape(tree.root()),
+            "(Module (ModuleDecl ModuleKw Identifier Semicolon) \
+             (Import ImportKw (Path Identifier Dot Identifier) Semicolon) \
+             (FuncDecl PubKw FuncKw Identifier (ParamList LParen RParen) Colon (TypeRef IntKw) \
+             (Block LBrace (CallExpr Identifier (ArgList LParen RParen)) RBrace)))"
+        );
+    }
+
+    #[test]
+    pub fn trivia_stays_outside_nodes() {
+        let tree = parse("  // doc\n  func f(): int { 1 }  \n");
+        let function = &tree.module().functions()[0];
+        assert_eq!(function.syntax().text(), "func f(): int { 1 }");
+        assert_eq!(function.span().start, (11, 1, 2).into());
+    }
+
+    #[test]
+    pub fn errors() {
+        let tree = parse("7 func f(): int { 1 2 } }");
+        assert_eq!(
+            shape(tree.root()),
+            "(Module (Error Integer) \
+             (FuncDecl FuncKw Identifier (ParamList LParen RParen) Colon (TypeRef IntKw) \
+             (Block LBrace (IntegerExpr Integer) (Error Integer) RBrace)) (Error RBrace))"
+        );
+
+        let tree = parse("func f(): int { ✨");
+        assert_eq!(
+            shape(tree.root()),
+            "(Module (FuncDecl FuncKw Identifier (ParamList LParen RParen) Colon (TypeRef IntKw) \
+             (Block LBrace (Error Unknown))))"
+        );
+    }
+
+    #[test]
+    pub fn views() {
+        let tree = parse("module m;\nimport a.b;\npub func f(): int { g() }\nfunc g(): int { 7 }");
+        let module = tree.module();
+
+        let name = module.declaration().and_then(|d| d.name()).unwrap();
+        assert_eq!(name.text(), "m");
+        let path: Vec<_> = module.imports()[0]
+            .path()
+            .iter()
+            .map(|t| t.text().to_owned())
+            .collect();
+        assert_eq!(path, vec!["a", "b"]);
+
+        let functions = module.functions();
+        assert!(functions[0].is_public());
+        assert!(!functions[1].is_public());
+        assert_eq!(functions[0].return_type().unwrap().text(), "int");
+        match functions[0].body().and_then(|b| b.expr()) {
+            Some(views::Expr::Call(call)) => assert_eq!(call.callee().unwrap().text(), "g"),
+            e => panic!("unexpected expression: {:?}", e),
+        }
+        match functions[1].body().and_then(|b| b.expr()) {
+            Some(views::Expr::Integer(i)) => assert_eq!(i.value(), Some(7)),
+            e => panic!("unexpected expression: {:?}", e),
+        }
+    }
+
+    #[test]
+    pub fn spans_match_ast() {
+        let mut sources = SourceDatabase::new();
+        let file = sources.add(
+            "test.spz",
+            "func a(): int {\r\n  b()\r\n}\u{2028}func b(): int { 𝒜() }\nfunc 𝒜(): int { 1 }",
+        );
+        let document = sources.document(file).unwrap();
+        let ast = parser::parse(document).unwrap();
+        let tree = SyntaxTree::parse(document);
+
+        let functions = tree.module().functions();
+        assert_eq!(functions.len(), ast.items.len());
+        for ((view, item), decl) in functions.iter().zip(&ast.items).zip(ast.functions()) {
+            assert_eq!(view.span(), item.span);
+            assert_eq!(view.name().unwrap().span(), decl.name.span);
+            assert_eq!(view.body().unwrap().span(), decl.body.span);
+            assert_eq!(view.span().file, file);
+        }
+    }
+
+    #[test]
+    pub fn reparse() {
+        let mut document = Document::new("func f(): int { 1 }\nfunc g(): int { 2 }");
+        let tree = SyntaxTree::parse(&document);
+
+        let edit = TextEdit::new(16..17, "f()");
+        document.apply(&edit);
+        let tree = tree.reparse(&edit, &document);
+        assert_eq!(tree.to_string(), document.text);
+        let g = &tree.module().functions()[1];
+        assert_eq!(g.span().start, (22, 1, 0).into());
+        match tree.module().functions()[0].body().and_then(|b| b.expr()) {
+            Some(views::Expr::Call(call)) => assert_eq!(call.callee().unwrap().text(), "f"),
+            e => panic!("unexpected expression: {:?}", e),
+        }
+    }
+
+    #[test]
+    pub fn navigation() {
+        let tree = parse("func f(): int { 1 }");
+        let function = tree.root().children().next().unwrap();
+        let block = function.child(SyntaxKind::Block).unwrap();
+        assert_eq!(block.parent(), Some(&function));
+        assert_eq!(function.parent(), Some(tree.root()));
+        assert_eq!(block.offset(), 14);
+
+        let tokens: Vec<_> = tree
+            .root()
+            .descendant_tokens()
+            .iter()
+            .map(|t| t.text().to_owned())
+            .collect();
+        assert_eq!(tokens.concat(), "func f(): int { 1 }");
+    }
+}
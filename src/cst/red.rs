@@ -0,0 +1,216 @@
+//! The red tree: a view of a green tree that knows where each node is
+//!
+//! Red nodes are created on demand while walking down from the root, and hold on to their parent
+//! so the walk can also go back up.
+
+use std::{rc::Rc, sync::Arc};
+
+use super::{
+    green::{GreenElement, GreenNode, GreenToken},
+    SyntaxKind,
+};
+use crate::{
+    ast::SyntaxNode,
+    text::{FileId, LineIndex, Span},
+};
+
+/// What every node in a tree needs to turn offsets into spans.
+#[derive(Debug)]
+struct Context {
+    file: FileId,
+    line_index: Arc<LineIndex>,
+}
+
+#[derive(Debug)]
+struct NodeData {
+    green: Arc<GreenNode>,
+    parent: Option<RedNode>,
+    offset: usize,
+    context: Rc<Context>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RedNode(Rc<NodeData>);
+
+impl RedNode {
+    /// Creates the root of a tree whose text starts at the start of `file`.
+    pub(super) fn new_root(
+        green: Arc<GreenNode>,
+        file: FileId,
+        line_index: Arc<LineIndex>,
+    ) -> RedNode {
+        RedNode(Rc::new(NodeData {
+            green,
+            parent: None,
+            offset: 0,
+            context: Rc::new(Context { file, line_index }),
+        }))
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.green.kind()
+    }
+
+    pub fn green(&self) -> &Arc<GreenNode> {
+        &self.0.green
+    }
+
+    pub fn parent(&self) -> Option<&RedNode> {
+        self.0.parent.as_ref()
+    }
+
+    /// Gets the byte offset of the node in the document.
+    pub fn offset(&self) -> usize {
+        self.0.offset
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.green.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn text(&self) -> String {
+        self.0.green.to_string()
+    }
+
+    pub fn children_with_tokens(&self) -> Vec<RedElement> {
+        let mut offset = self.offset();
+        let mut children = Vec::with_capacity(self.0.green.children().len());
+        for child in self.0.green.children() {
+            children.push(match child {
+                GreenElement::Node(green) => RedElement::Node(RedNode(Rc::new(NodeData {
+                    green: green.clone(),
+                    parent: Some(self.clone()),
+                    offset,
+                    context: self.0.context.clone(),
+                }))),
+                GreenElement::Token(green) => RedElement::Token(RedToken {
+                    green: green.clone(),
+                    parent: self.clone(),
+                    offset,
+                }),
+            });
+            offset += child.len();
+        }
+        children
+    }
+
+    /// Finds the first direct child node of the given kind.
+    pub fn child(&self, kind: SyntaxKind) -> Option<RedNode> {
+        self.children().find(|n| n.kind() == kind)
+    }
+
+    /// Finds the first token of the given kind among the node's direct children.
+    pub fn token(&self, kind: SyntaxKind) -> Option<RedToken> {
+        self.children_with_tokens()
+            .into_iter()
+            .find_map(|child| match child {
+                RedElement::Token(t) if t.kind() == kind => Some(t),
+                _ => None,
+            })
+    }
+
+    /// Gets every token in the node, including trivia, in order.
+    pub fn descendant_tokens(&self) -> Vec<RedToken> {
+        let mut tokens = Vec::new();
+        for child in self.children_with_tokens() {
+            match child {
+                RedElement::Node(n) => tokens.extend(n.descendant_tokens()),
+                RedElement::Token(t) => tokens.push(t),
+            }
+        }
+        tokens
+    }
+
+    fn span_of(&self, start: usize, end: usize) -> Span {
+        let context = &self.0.context;
+        Span::in_file(
+            context.file,
+            context.line_index.location(start),
+            context.line_index.location(end),
+        )
+    }
+}
+
+impl PartialEq for RedNode {
+    fn eq(&self, other: &RedNode) -> bool {
+        Arc::ptr_eq(&self.0.green, &other.0.green) && self.0.offset == other.0.offset
+    }
+}
+
+impl Eq for RedNode {}
+
+impl SyntaxNode for RedNode {
+    type Children = RedNode;
+    type Iter = std::vec::IntoIter<RedNode>;
+
+    fn span(&self) -> Span {
+        self.span_of(self.offset(), self.offset() + self.len())
+    }
+
+    /// Gets the child nodes, leaving out tokens.
+    fn children(&self) -> Self::Iter {
+        self.children_with_tokens()
+            .into_iter()
+            .filter_map(|child| match child {
+                RedElement::Node(n) => Some(n),
+                RedElement::Token(_) => None,
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
+
+impl std::fmt::Display for RedNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0.green)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedToken {
+    green: Arc<GreenToken>,
+    parent: RedNode,
+    offset: usize,
+}
+
+impl RedToken {
+    pub fn kind(&self) -> SyntaxKind {
+        self.green.kind()
+    }
+
+    pub fn text(&self) -> &str {
+        self.green.text()
+    }
+
+    pub fn parent(&self) -> &RedNode {
+        &self.parent
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn span(&self) -> Span {
+        self.parent
+            .span_of(self.offset, self.offset + self.green.len())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RedElement {
+    Node(RedNode),
+    Token(RedToken),
+}
+
+impl RedElement {
+    pub fn kind(&self) -> SyntaxKind {
+        match self {
+            RedElement::Node(n) => n.kind(),
+            RedElement::Token(t) => t.kind(),
+        }
+    }
+}
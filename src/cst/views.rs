@@ -0,0 +1,154 @@
+//! Typed views over the red tree
+//!
+//! Each view wraps a node of one kind and has accessors for the parts of it, mirroring the AST.
+//! Since the tree may be missing parts that didn't parse, most accessors return an `Option`.
+
+use super::{
+    red::{RedNode, RedToken},
+    SyntaxKind,
+};
+use crate::{ast::SyntaxNode, text::Span};
+
+macro_rules! view {
+    ($name: ident) => {
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub struct $name(RedNode);
+
+        impl $name {
+            pub fn cast(node: RedNode) -> Option<$name> {
+                if node.kind() == SyntaxKind::$name {
+                    Some($name(node))
+                } else {
+                    None
+                }
+            }
+
+            pub fn syntax(&self) -> &RedNode {
+                &self.0
+            }
+        }
+
+        impl SyntaxNode for $name {
+            type Children = RedNode;
+            type Iter = std::vec::IntoIter<RedNode>;
+
+            fn span(&self) -> Span {
+                self.0.span()
+            }
+
+            fn children(&self) -> Self::Iter {
+                self.0.children()
+            }
+        }
+    };
+}
+
+view!(Module);
+view!(ModuleDecl);
+view!(Import);
+view!(FuncDecl);
+view!(Block);
+view!(IntegerExpr);
+view!(CallExpr);
+
+fn cast_children<T>(node: &RedNode, cast: fn(RedNode) -> Option<T>) -> Vec<T> {
+    node.children().filter_map(cast).collect()
+}
+
+impl Module {
+    pub fn declaration(&self) -> Option<ModuleDecl> {
+        self.0
+            .child(SyntaxKind::ModuleDecl)
+            .and_then(ModuleDecl::cast)
+    }
+
+    pub fn imports(&self) -> Vec<Import> {
+        cast_children(&self.0, Import::cast)
+    }
+
+    pub fn functions(&self) -> Vec<FuncDecl> {
+        cast_children(&self.0, FuncDecl::cast)
+    }
+}
+
+impl ModuleDecl {
+    pub fn name(&self) -> Option<RedToken> {
+        self.0.token(SyntaxKind::Identifier)
+    }
+}
+
+impl Import {
+    /// Gets the segments of the imported path.
+    pub fn path(&self) -> Vec<RedToken> {
+        self.0
+            .child(SyntaxKind::Path)
+            .map(|path| {
+                path.descendant_tokens()
+                    .into_iter()
+                    .filter(|t| t.kind() == SyntaxKind::Identifier)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl FuncDecl {
+    pub fn is_public(&self) -> bool {
+        self.0.token(SyntaxKind::PubKw).is_some()
+    }
+
+    pub fn name(&self) -> Option<RedToken> {
+        self.0.token(SyntaxKind::Identifier)
+    }
+
+    pub fn return_type(&self) -> Option<RedToken> {
+        self.0
+            .child(SyntaxKind::TypeRef)
+            .and_then(|t| t.token(SyntaxKind::IntKw))
+    }
+
+    pub fn body(&self) -> Option<Block> {
+        self.0.child(SyntaxKind::Block).and_then(Block::cast)
+    }
+}
+
+impl Block {
+    pub fn expr(&self) -> Option<Expr> {
+        self.0.children().find_map(Expr::cast)
+    }
+}
+
+impl IntegerExpr {
+    pub fn value(&self) -> Option<i128> {
+        self.0.token(SyntaxKind::Integer)?.text().parse().ok()
+    }
+}
+
+impl CallExpr {
+    pub fn callee(&self) -> Option<RedToken> {
+        self.0.token(SyntaxKind::Identifier)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Integer(IntegerExpr),
+    Call(CallExpr),
+}
+
+impl Expr {
+    pub fn cast(node: RedNode) -> Option<Expr> {
+        match node.kind() {
+            SyntaxKind::IntegerExpr => Some(Expr::Integer(IntegerExpr(node))),
+            SyntaxKind::CallExpr => Some(Expr::Call(CallExpr(node))),
+            _ => None,
+        }
+    }
+
+    pub fn syntax(&self) -> &RedNode {
+        match self {
+            Expr::Integer(e) => e.syntax(),
+            Expr::Call(e) => e.syntax(),
+        }
+    }
+}
@@ -0,0 +1,237 @@
+//! Rendering errors against the source text they came from
+
+use std::fmt::Write;
+
+use crate::text::{Document, SourceDatabase, Span, Spanned};
+
+/// How serious a diagnostic is. Errors stop a program from compiling, while warnings point out
+/// code that's likely a mistake.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Option<Span>,
+    pub notes: Vec<Spanned<String>>,
+}
+
+impl Diagnostic {
+    pub fn error<S: Into<String>>(message: S, span: Option<Span>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            message: message.into(),
+            span,
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn warning<S: Into<String>>(message: S, span: Option<Span>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(message, span)
+        }
+    }
+
+    pub fn with_note<S: Into<String>>(mut self, span: Span, message: S) -> Diagnostic {
+        self.notes.push(Spanned::new(message.into(), span));
+        self
+    }
+
+    /// Renders the diagnostic, quoting the lines it refers to from whichever files in `sources`
+    /// they belong to.
+    pub fn render(&self, sources: &SourceDatabase) -> String {
+        let mut out = String::new();
+        let severity = self.severity.to_string();
+        write_section(&mut out, &severity, &self.message, self.span, sources)
+            .expect("writing to a String cannot fail");
+        for note in &self.notes {
+            out.push('\n');
+            write_section(&mut out, "note", &note.value, Some(note.span), sources)
+                .expect("writing to a String cannot fail");
+        }
+        out
+    }
+}
+
+fn write_section(
+    out: &mut String,
+    severity: &str,
+    message: &str,
+    span: Option<Span>,
+    sources: &SourceDatabase,
+) -> std::fmt::Result {
+    write!(out, "{}: {}", severity, message)?;
+    let span = match span {
+        Some(span) => span,
+        None => return Ok(()),
+    };
+
+    let line_number = (span.start.line + 1).to_string();
+    let gutter = " ".repeat(line_number.len());
+    let (name, document) = match (sources.name(span.file), sources.document(span.file)) {
+        (Some(name), Some(document)) => (name, document),
+        // Without the text there's nothing to quote.
+        _ => return write!(out, "\n{}--> <unknown>:{}", gutter, span.start),
+    };
+    let text = line_at(document, span.start.offset);
+
+    // Spans covering several lines are underlined up to the end of their first line.
+    let width = if span.end.line == span.start.line {
+        span.end.column - span.start.column
+    } else {
+        text.chars().count().saturating_sub(span.start.column)
+    };
+
+    writeln!(out)?;
+    writeln!(out, "{}--> {}:{}", gutter, name, span.start)?;
+    writeln!(out, "{} |", gutter)?;
+    writeln!(out, "{} | {}", line_number, text)?;
+    write!(
+        out,
+        "{} | {}{}",
+        gutter,
+        " ".repeat(span.start.column),
+        "^".repeat(width.max(1))
+    )
+}
+
+/// Gets the text of the line containing `offset`, without its line terminator.
+fn line_at(document: &Document, offset: usize) -> &str {
+    let index = document.line_index();
+    let range = index
+        .line_range(index.line(offset))
+        .expect("offset is within the document");
+    document.text[range].trim_end_matches('\r')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::FileId;
+
+    fn span(file: FileId, start: (usize, usize, usize), end: (usize, usize, usize)) -> Span {
+        Span::in_file(file, start.into(), end.into())
+    }
+
+    #[test]
+    pub fn render_single_line() {
+        let mut sources = SourceDatabase::new();
+        let file = sources.add("test.spz", "func main(): int {\n    nope()\n}");
+        let diagnostic = Diagnostic::error(
+            "unknown function 'nope'",
+            Some(span(file, (23, 1, 4), (27, 1, 8))),
+        );
+
+        assert_eq!(
+            diagnostic.render(&sources),
+            "error: unknown function 'nope'\n\
+             \x20--> test.spz:2:5\n\
+             \x20 |\n\
+             2 |     nope()\n\
+             \x20 |     ^^^^"
+        );
+    }
+
+    #[test]
+    pub fn render_multi_line() {
+        let mut sources = SourceDatabase::new();
+        let file = sources.add("test.spz", "func main(): int {\r\n    42\r\n}");
+        let diagnostic = Diagnostic::error("bad block", Some(span(file, (17, 0, 17), (29, 2, 1))));
+
+        assert_eq!(
+            diagnostic.render(&sources),
+            "error: bad block\n\
+             \x20--> test.spz:1:18\n\
+             \x20 |\n\
+             1 | func main(): int {\n\
+             \x20 |                  ^"
+        );
+    }
+
+    #[test]
+    pub fn render_without_span() {
+        let diagnostic = Diagnostic::error("unexpected end of file", None);
+        assert_eq!(
+            diagnostic.render(&SourceDatabase::new()),
+            "error: unexpected end of file"
+        );
+    }
+
+    #[test]
+    pub fn render_unknown_file() {
+        let diagnostic = Diagnostic::error(
+            "unknown function 'nope'",
+            Some(span(FileId::DETACHED, (23, 1, 4), (27, 1, 8))),
+        );
+        assert_eq!(
+            diagnostic.render(&SourceDatabase::new()),
+            "error: unknown function 'nope'\n\
+             \x20--> <unknown>:2:5"
+        );
+    }
+
+    #[test]
+    pub fn render_note() {
+        let mut sources = SourceDatabase::new();
+        let file = sources.add("test.spz", "func a(): int { 1 }\nfunc a(): int { 2 }");
+        let diagnostic = Diagnostic::error(
+            "function 'a' is already defined",
+            Some(span(file, (25, 1, 5), (26, 1, 6))),
+        )
+        .with_note(span(file, (5, 0, 5), (6, 0, 6)), "previously defined here");
+
+        assert_eq!(
+            diagnostic.render(&sources),
+            "error: function 'a' is already defined\n\
+             \x20--> test.spz:2:6\n\
+             \x20 |\n\
+             2 | func a(): int { 2 }\n\
+             \x20 |      ^\n\
+             note: previously defined here\n\
+             \x20--> test.spz:1:6\n\
+             \x20 |\n\
+             1 | func a(): int { 1 }\n\
+             \x20 |      ^"
+        );
+    }
+
+    #[test]
+    pub fn render_note_in_other_file() {
+        let mut sources = SourceDatabase::new();
+        let first = sources.add("first.spz", "func a(): int { 1 }");
+        let second = sources.add("second.spz", "\n\nfunc a(): int { 2 }");
+        let diagnostic = Diagnostic::error(
+            "function 'a' is already defined",
+            Some(span(second, (7, 2, 5), (8, 2, 6))),
+        )
+        .with_note(span(first, (5, 0, 5), (6, 0, 6)), "previously defined here");
+
+        assert_eq!(
+            diagnostic.render(&sources),
+            "error: function 'a' is already defined\n\
+             \x20--> second.spz:3:6\n\
+             \x20 |\n\
+             3 | func a(): int { 2 }\n\
+             \x20 |      ^\n\
+             note: previously defined here\n\
+             \x20--> first.spz:1:6\n\
+             \x20 |\n\
+             1 | func a(): int { 1 }\n\
+             \x20 |      ^"
+        );
+    }
+}
@@ -0,0 +1,261 @@
+//! Pretty-printing source in the canonical style
+//!
+//! Declarations are separated by a blank line, imports are grouped together, and function bodies
+//! are indented by four spaces with the opening brace on the same line as the signature:
+//!
+//! ```text
+//! func main(): int {
+//!     42
+//! }
+//! ```
+//!
+//! Comments stay in front of whatever followed them, or at the end of the line they were on if
+//! they came after code.
+
+use crate::{
+    ast::{Expr, Ident, Item, Module, Visibility},
+    parser::{self, ParserError},
+    text::{Document, Span, Spanned},
+};
+
+const INDENT: &str = "    ";
+
+/// Formats `document`, which must parse.
+pub fn format(document: &Document) -> Result<String, ParserError> {
+    let (module, comments) = parser::parse_with_comments(document)?;
+    Ok(print_with_comments(&module, comments))
+}
+
+/// Prints `module` as source in the canonical style. There are no comments in the AST, so none
+/// are printed.
+///
+/// Panics if the module has `Error` nodes left from a failed parse.
+pub fn print(module: &Module) -> String {
+    print_with_comments(module, Vec::new())
+}
+
+fn print_with_comments(module: &Module, comments: Vec<Spanned<String>>) -> String {
+    let mut printer = Printer {
+        out: String::new(),
+        comments: comments.into_iter().peekable(),
+        indent: 0,
+        last_line: None,
+    };
+    printer.module(module);
+    printer.out
+}
+
+struct Printer {
+    out: String,
+    comments: std::iter::Peekable<std::vec::IntoIter<Spanned<String>>>,
+    indent: usize,
+    /// The line in the source of the last thing printed, so comments after it can stay there.
+    last_line: Option<usize>,
+}
+
+impl Printer {
+    fn module(&mut self, module: &Module) {
+        if let Some(name) = &module.name {
+            self.comments_before(name.span.start.offset);
+            self.line(&format!("module {};", Ident(name.value)), name.span);
+        }
+
+        for (i, import) in module.imports.iter().enumerate() {
+            if i == 0 && module.name.is_some() {
+                self.blank_line(import.span.start.offset);
+            }
+            self.comments_before(import.span.start.offset);
+            self.line(&format!("import {};", import.value), import.span);
+        }
+
+        for (i, item) in module.items.iter().enumerate() {
+            if i > 0 || module.name.is_some() || !module.imports.is_empty() {
+                self.blank_line(item.span.start.offset);
+            }
+            self.comments_before(item.span.start.offset);
+            self.item(item);
+        }
+
+        self.comments_before(usize::MAX);
+    }
+
+    fn item(&mut self, item: &Spanned<Item>) {
+        let decl = match &item.value {
+            Item::Func(decl) => decl,
+            Item::Error => panic!("cannot print a module with syntax errors"),
+        };
+        let visibility = match decl.visibility {
+            Visibility::Public => "pub ",
+            Visibility::Private => "",
+        };
+        let body = &decl.body;
+        self.line(
+            &format!(
+                "{}func {}(): {} {{",
+                visibility,
+                Ident(decl.name.value),
+                decl.return_type.value
+            ),
+            Span::new(item.span.start, body.span.start),
+        );
+
+        self.indent += 1;
+        let expr = &body.value.expr;
+        self.comments_before(expr.span.start.offset);
+        self.line(&self::expr(&expr.value), expr.span);
+        // Comments before the closing brace still belong inside the block.
+        self.comments_before(body.span.end.offset - 1);
+        self.indent -= 1;
+
+        self.line("}", Span::new(body.span.end, body.span.end));
+    }
+
+    fn line(&mut self, text: &str, span: Span) {
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+        self.last_line = Some(span.end.line);
+    }
+
+    /// Prints a blank line before whatever is at `next` in the source.
+    fn blank_line(&mut self, next: usize) {
+        // Comments at the end of the previous line go before the blank line, not after it.
+        self.trailing_comments(next);
+        self.out.push('\n');
+    }
+
+    /// Prints the comments that came before `offset` in the source.
+    fn comments_before(&mut self, offset: usize) {
+        self.trailing_comments(offset);
+        while let Some(comment) = self.comments.next_if(|c| c.span.start.offset < offset) {
+            self.line(&format!("//{}", comment.value), comment.span);
+            // Only comments after code stay on their line, so a second one starts a new line.
+            self.last_line = None;
+        }
+    }
+
+    /// Prints the comment on the same source line as the last thing printed at the end of it, if
+    /// it comes before `next` in the source. One after `next` follows whatever is printed there.
+    fn trailing_comments(&mut self, next: usize) {
+        let line = match self.last_line {
+            Some(line) => line,
+            None => return,
+        };
+        if let Some(comment) = self
+            .comments
+            .next_if(|c| c.span.start.line == line && c.span.start.offset < next)
+        {
+            self.out.pop();
+            self.out.push_str(&format!(" //{}\n", comment.value));
+            self.last_line = None;
+        }
+    }
+}
+
+fn expr(expr: &Expr) -> String {
+    match expr {
+        Expr::Integer(i) => i.to_string(),
+        Expr::Call(name) => format!("{}()", Ident(name.value)),
+        Expr::Error => panic!("cannot print a module with syntax errors"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fmt(source: &str) -> String {
+        format(&Document::new(source)).unwrap()
+    }
+
+    /// Formats `source`, checking that formatting the result again changes nothing.
+    fn assert_formats(source: &str, expected: &str) {
+        let formatted = fmt(source);
+        assert_eq!(formatted, expected);
+        assert_eq!(fmt(&formatted), formatted, "formatting is not idempotent");
+    }
+
+    #[test]
+    pub fn format_example() {
+        let example = include_str!("../examples/exit_code.spz");
+        assert_eq!(fmt(example), example);
+    }
+
+    #[test]
+    pub fn format_layout() {
+        assert_formats(
+            "func main ( ) :int{answer()}   func answer():int\n{\n42}",
+            "func main(): int {\n    answer()\n}\n\nfunc answer(): int {\n    42\n}\n",
+        );
+    }
+
+    #[test]
+    pub fn format_module_header() {
+        assert_formats(
+            "module main; import util;import shapes . square;\npub func main(): int { 1 }",
+            "module main;\n\nimport util;\nimport shapes.square;\n\npub func main(): int {\n    1\n}\n",
+        );
+    }
+
+    #[test]
+    pub fn format_raw_identifiers() {
+        assert_formats(
+            "module r#int; import r#pub.r#x;\nfunc r#func(): int { r#func() }",
+            "module r#int;\n\nimport r#pub.x;\n\nfunc r#func(): int {\n    r#func()\n}\n",
+        );
+    }
+
+    #[test]
+    pub fn format_empty() {
+        assert_formats("", "");
+        assert_formats("  // nothing here\r\n", "// nothing here\n");
+    }
+
+    #[test]
+    pub fn format_comments() {
+        assert_formats(
+            "// The program.\n\n\n// Entry point.\nfunc main(): int { // Starts here.\n\
+             // The answer.\n  42 // Always.\n  // Done.\n} // Main.\n\
+             func other(): int { 1 } // Other.\n// The end.",
+            "// The program.\n\
+             // Entry point.\n\
+             func main(): int { // Starts here.\n\
+             \x20   // The answer.\n\
+             \x20   42 // Always.\n\
+             \x20   // Done.\n\
+             } // Main.\n\
+             \n\
+             func other(): int {\n\
+             \x20   1\n\
+             } // Other.\n\
+             // The end.\n",
+        );
+    }
+
+    #[test]
+    pub fn format_comment_line_endings() {
+        assert_formats(
+            "// c\rfunc main(): int { 3 }",
+            "// c\nfunc main(): int {\n    3\n}\n",
+        );
+        assert_formats(
+            "func main(): int { // c\u{2028}3 }",
+            "func main(): int { // c\n    3\n}\n",
+        );
+    }
+
+    #[test]
+    pub fn format_comments_in_signature() {
+        assert_formats(
+            "func main() // Odd.\n: int { 1 }",
+            "func main(): int {\n    // Odd.\n    1\n}\n",
+        );
+    }
+
+    #[test]
+    pub fn format_parse_error() {
+        assert!(format(&Document::new("func main(): int {")).is_err());
+    }
+}
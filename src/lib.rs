@@ -1,7 +1,18 @@
 // Disable dead_code warnings on debug builds, while we're developing things.
 #![cfg_attr(debug_assertions, allow(dead_code))]
 
-mod ast;
-mod parser;
-mod text;
-mod tokens;
+pub mod ast;
+pub mod bytecode;
+pub mod cst;
+pub mod diagnostics;
+pub mod formatter;
+pub mod lints;
+pub mod lsp;
+pub mod modules;
+pub mod parser;
+pub mod repl;
+pub mod sexpr;
+pub mod symbol;
+pub mod text;
+pub mod tokens;
+pub mod visit;
@@ -0,0 +1,121 @@
+//! Warnings about code that compiles but is likely a mistake
+
+use std::collections::{HashMap, HashSet};
+
+use unicode_security::confusable_detection::skeleton;
+
+use crate::{
+    ast::{Expr, Import, Item, Module},
+    diagnostics::Diagnostic,
+    symbol::Symbol,
+    text::Spanned,
+    visit::{self, Node, Visitor},
+};
+
+/// Runs every lint over `module`.
+pub fn check(module: &Module) -> Vec<Diagnostic> {
+    confusable_identifiers(module)
+}
+
+/// Warns about names that differ but look alike, such as `scope` spelled with a Cyrillic `о`, by
+/// comparing their skeletons as defined by Unicode Technical Standard #39.
+pub fn confusable_identifiers(module: &Module) -> Vec<Diagnostic> {
+    let mut names = Names::default();
+    names.visit_module(module);
+
+    let mut seen: HashMap<String, Spanned<Symbol>> = HashMap::new();
+    let mut warnings = Vec::new();
+    for name in names.first_uses {
+        let key: String = skeleton(&name.value.to_string()).collect();
+        match seen.get(&key) {
+            Some(other) => warnings.push(
+                Diagnostic::warning(
+                    format!(
+                        "identifier '{}' looks like '{}' but is different",
+                        name.value, other.value
+                    ),
+                    Some(name.span),
+                )
+                .with_note(other.span, format!("'{}' is used here", other.value)),
+            ),
+            None => {
+                seen.insert(key, name);
+            }
+        }
+    }
+    warnings
+}
+
+/// Collects the first use of each name in a module, in order.
+#[derive(Default)]
+struct Names {
+    first_uses: Vec<Spanned<Symbol>>,
+    seen: HashSet<Symbol>,
+}
+
+impl Names {
+    fn add(&mut self, name: &Spanned<Symbol>) {
+        if self.seen.insert(name.value) {
+            self.first_uses.push(name.clone());
+        }
+    }
+}
+
+impl<'a> Visitor<'a> for Names {
+    fn visit_module(&mut self, module: &'a Module) {
+        if let Some(name) = &module.name {
+            self.add(name);
+        }
+        visit::walk(self, Node::Module(module));
+    }
+
+    fn visit_import(&mut self, import: &'a Spanned<Import>) {
+        for segment in &import.value.path {
+            self.add(segment);
+        }
+    }
+
+    fn visit_item(&mut self, item: &'a Spanned<Item>) {
+        if let Item::Func(decl) = &item.value {
+            self.add(&decl.name);
+        }
+        visit::walk(self, Node::Item(item));
+    }
+
+    fn visit_expr(&mut self, expr: &'a Spanned<Expr>) {
+        if let Expr::Call(callee) = &expr.value {
+            self.add(callee);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{diagnostics::Severity, parser, text::Document};
+
+    fn lint(text: &str) -> Vec<Diagnostic> {
+        confusable_identifiers(&parser::parse(&Document::new(text)).unwrap())
+    }
+
+    #[test]
+    pub fn confusable() {
+        // The second `scope` has a Cyrillic `о`.
+        let warnings = lint("func scope(): int { 1 }\nfunc main(): int { sc\u{43E}pe() }");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].severity, Severity::Warning);
+        assert_eq!(
+            warnings[0].message,
+            "identifier 'sc\u{43E}pe' looks like 'scope' but is different"
+        );
+        assert_eq!(warnings[0].span.unwrap().start, (43, 1, 19).into());
+        assert_eq!(warnings[0].notes[0].span.start, (5, 0, 5).into());
+    }
+
+    #[test]
+    pub fn not_confusable() {
+        assert!(lint("func f(): int { 1 }\nfunc main(): int { f() }").is_empty());
+        assert!(lint("import a.b;\nfunc a(): int { b() }\nfunc b(): int { 1 }").is_empty());
+        assert!(lint("func l(): int { 1 }\nfunc main(): int { 1 }").is_empty());
+    }
+}
@@ -0,0 +1,831 @@
+//! Language Server Protocol support
+//!
+//! The server speaks JSON-RPC over any reader and writer, usually stdin and stdout. Documents are
+//! synchronized in full on every change and reparsed for each request, which is plenty fast for
+//! the size of programs we have today. Documents only treat the line endings the protocol knows
+//! about as ending lines, so our positions agree with the client's.
+
+pub mod transport;
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    ast::{Expr, FuncDecl, Item, Module, Token, Type},
+    bytecode::compiler,
+    diagnostics::{Diagnostic, Severity},
+    modules::{self, Loader},
+    parser,
+    symbol::Session,
+    text::{ColumnUnit, Document, LineEndings, Location, SourceDatabase, Span, Window},
+    tokens::TokenBuffer,
+};
+use transport::{read_message, write_message, TransportError};
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+
+/// The semantic token types we report, in the order of their indices in the legend.
+pub const SEMANTIC_TOKEN_TYPES: &[&str] = &["keyword", "function", "type", "number"];
+
+const SYMBOL_KIND_FUNCTION: u32 = 12;
+const SEVERITY_ERROR: u32 = 1;
+const SEVERITY_WARNING: u32 = 2;
+
+/// Serves requests from `input` until the client sends `exit` or closes the stream.
+pub fn run<R: BufRead, W: Write>(mut input: R, mut output: W) -> Result<(), TransportError> {
+    let mut server = Server::default();
+    loop {
+        let message = match read_message(&mut input) {
+            Ok(Some(message)) => message,
+            Ok(None) => return Ok(()),
+            Err(TransportError::InvalidJson(e)) => {
+                let response = error_response(Value::Null, PARSE_ERROR, e.to_string());
+                write_message(&mut output, &response)?;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+
+        // Nothing is kept from one message to the next but the text of documents, so the names
+        // interned while handling one can be freed straight after.
+        for reply in Session::new().enter(|| server.handle(&message)) {
+            write_message(&mut output, &reply)?;
+        }
+        if server.exited {
+            return Ok(());
+        }
+    }
+}
+
+struct ResponseError {
+    code: i64,
+    message: String,
+}
+
+impl ResponseError {
+    fn invalid_params<S: Into<String>>(message: S) -> ResponseError {
+        ResponseError {
+            code: INVALID_PARAMS,
+            message: message.into(),
+        }
+    }
+}
+
+fn error_response<S: Into<String>>(id: Value, code: i64, message: S) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message.into() },
+    })
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+#[derive(Default)]
+pub struct Server {
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+    exited: bool,
+}
+
+impl Server {
+    /// Handles a single message from the client, returning any messages to send back.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let params = message.get("params").unwrap_or(&Value::Null);
+        match (message.get("id"), message["method"].as_str()) {
+            (Some(id), Some(method)) => vec![self.request(id.clone(), method, params)],
+            (None, Some(method)) => self.notification(method, params),
+            // We never send requests, so there are no responses to handle.
+            _ => Vec::new(),
+        }
+    }
+
+    fn request(&mut self, id: Value, method: &str, params: &Value) -> Value {
+        if self.shutdown {
+            return error_response(id, INVALID_REQUEST, "the server is shutting down");
+        }
+
+        let result = match method {
+            "initialize" => Ok(initialize_result()),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "textDocument/hover" => self.hover(params),
+            "textDocument/definition" => self.definition(params),
+            "textDocument/documentSymbol" => self.document_symbols(params),
+            "textDocument/semanticTokens/full" => self.semantic_tokens(params),
+            _ => Err(ResponseError {
+                code: METHOD_NOT_FOUND,
+                message: format!("unknown method '{}'", method),
+            }),
+        };
+
+        match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => error_response(id, e.code, e.message),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"].as_str().map(str::to_owned);
+        match (method, uri) {
+            ("exit", _) => {
+                self.exited = true;
+                Vec::new()
+            }
+            ("textDocument/didOpen", Some(uri)) => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(
+                    uri.clone(),
+                    Document::with_line_endings(text, LineEndings::Ascii),
+                );
+                vec![self.publish_diagnostics(&uri)]
+            }
+            ("textDocument/didChange", Some(uri)) => {
+                // We only ask for full synchronization, so the last change is the whole text.
+                let text = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+                match text {
+                    Some(text) => {
+                        self.documents.insert(
+                            uri.clone(),
+                            Document::with_line_endings(text, LineEndings::Ascii),
+                        );
+                        vec![self.publish_diagnostics(&uri)]
+                    }
+                    None => Vec::new(),
+                }
+            }
+            ("textDocument/didClose", Some(uri)) => {
+                self.documents.remove(&uri);
+                vec![notification(
+                    "textDocument/publishDiagnostics",
+                    json!({ "uri": uri, "diagnostics": [] }),
+                )]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn document(&self, params: &Value) -> Result<(&str, &Document), ResponseError> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .ok_or_else(|| ResponseError::invalid_params("missing textDocument.uri"))?;
+        self.documents
+            .get_key_value(uri)
+            .map(|(uri, document)| (uri.as_str(), document))
+            .ok_or_else(|| ResponseError::invalid_params(format!("'{}' is not open", uri)))
+    }
+
+    fn publish_diagnostics(&self, uri: &str) -> Value {
+        let document = &self.documents[uri];
+        let mut sources = SourceDatabase::with_line_endings(LineEndings::Ascii);
+        let errors = self.check(&mut sources, uri);
+
+        // The document is the first file loaded. Errors in the modules it imports are reported
+        // when those are open instead.
+        let file = sources.files().next();
+        let diagnostics: Vec<_> = errors
+            .iter()
+            .filter(|d| d.span.is_none_or(|span| Some(span.file) == file))
+            .map(|d| self.to_lsp_diagnostic(document, &sources, d))
+            .collect();
+        notification(
+            "textDocument/publishDiagnostics",
+            json!({ "uri": uri, "diagnostics": diagnostics }),
+        )
+    }
+
+    /// Parses and checks the document at `uri` along with everything it imports, returning any
+    /// errors and warnings found.
+    fn check(&self, sources: &mut SourceDatabase, uri: &str) -> Vec<Diagnostic> {
+        let mut workspace = Workspace {
+            documents: &self.documents,
+        };
+        let graph = match modules::load(sources, &mut workspace, &uri_path(uri)) {
+            Ok(graph) => graph,
+            Err(errors) => return errors.iter().map(Diagnostic::from).collect(),
+        };
+        let checked = match graph.link() {
+            Ok(module) => compiler::check(&module).map_err(|e| Diagnostic::from(&e)),
+            Err(e) => Err(Diagnostic::from(&e)),
+        };
+        let mut diagnostics = graph.lint();
+        diagnostics.extend(checked.err());
+        diagnostics
+    }
+
+    fn to_lsp_diagnostic(
+        &self,
+        document: &Document,
+        sources: &SourceDatabase,
+        diagnostic: &Diagnostic,
+    ) -> Value {
+        // Errors without a location, like an unexpected end of file, are reported at the very end.
+        let span = diagnostic.span.unwrap_or_else(|| end_of(document));
+        let related: Vec<_> = diagnostic
+            .notes
+            .iter()
+            .filter_map(|note| {
+                let name = sources.name(note.span.file)?;
+                let note_document = sources.document(note.span.file)?;
+                let note_uri = self
+                    .uri_of(Path::new(name))
+                    .map_or_else(|| path_uri(name), str::to_owned);
+                Some(json!({
+                    "location": { "uri": note_uri, "range": range(note_document, note.span) },
+                    "message": note.value,
+                }))
+            })
+            .collect();
+        json!({
+            "range": range(document, span),
+            "severity": match diagnostic.severity {
+                Severity::Error => SEVERITY_ERROR,
+                Severity::Warning => SEVERITY_WARNING,
+            },
+            "source": "spuzc",
+            "message": diagnostic.message,
+            "relatedInformation": related,
+        })
+    }
+
+    /// Finds the open document for `path`, if there is one.
+    fn uri_of(&self, path: &Path) -> Option<&str> {
+        self.documents
+            .keys()
+            .find(|uri| uri_path(uri) == path)
+            .map(String::as_str)
+    }
+
+    fn hover(&self, params: &Value) -> Result<Value, ResponseError> {
+        let (_, document) = self.document(params)?;
+        let offset = offset_param(document, params)?;
+        // Whatever did parse is still worth looking at, errors or not.
+        let (module, _) = parser::parse_recovering(document);
+
+        let (contents, span) = match target_at(&module, offset) {
+            Some(Target::Function(decl, span)) => (signature(decl), span),
+            Some(Target::Type(ty, span)) => (ty.to_string(), span),
+            Some(Target::Integer(span)) => (Type::Int.to_string(), span),
+            None => return Ok(Value::Null),
+        };
+        Ok(json!({
+            "contents": {
+                "kind": "markdown",
+                "value": format!("```spuzzum\n{}\n```", contents),
+            },
+            "range": range(document, span),
+        }))
+    }
+
+    fn definition(&self, params: &Value) -> Result<Value, ResponseError> {
+        let (uri, document) = self.document(params)?;
+        let offset = offset_param(document, params)?;
+        let (module, _) = parser::parse_recovering(document);
+
+        match target_at(&module, offset) {
+            Some(Target::Function(decl, _)) => Ok(json!({
+                "uri": uri,
+                "range": range(document, decl.name.span),
+            })),
+            _ => Ok(Value::Null),
+        }
+    }
+
+    fn document_symbols(&self, params: &Value) -> Result<Value, ResponseError> {
+        let (_, document) = self.document(params)?;
+        let (module, _) = parser::parse_recovering(document);
+
+        let symbols: Vec<_> = module
+            .items
+            .iter()
+            .filter_map(|item| match &item.value {
+                Item::Func(decl) => Some((item, decl)),
+                Item::Error => None,
+            })
+            .map(|(item, decl)| {
+                json!({
+                    "name": decl.name.value.to_string(),
+                    "detail": format!("(): {}", decl.return_type.value),
+                    "kind": SYMBOL_KIND_FUNCTION,
+                    "range": range(document, item.span),
+                    "selectionRange": range(document, decl.name.span),
+                })
+            })
+            .collect();
+        Ok(Value::Array(symbols))
+    }
+
+    fn semantic_tokens(&self, params: &Value) -> Result<Value, ResponseError> {
+        let (_, document) = self.document(params)?;
+        let mut buffer = TokenBuffer::new(Window::new(document));
+
+        let mut data = Vec::new();
+        let (mut previous_line, mut previous_start) = (0, 0);
+        // Stop at the first lexical error, everything before it is still worth highlighting.
+        while let Ok(token) = buffer.take_token() {
+            let token_type = match token.value {
+                Token::Func | Token::Module | Token::Import | Token::Pub => 0,
+                Token::Identifier(_) => 1,
+                Token::Int => 2,
+                Token::Integer(_) => 3,
+                _ => continue,
+            };
+
+            let start = position(document, token.span.start);
+            let end = position(document, token.span.end);
+            let (line, character) = (start.0, start.1);
+            let delta_start = if line == previous_line {
+                character - previous_start
+            } else {
+                character
+            };
+            data.extend_from_slice(&[
+                line - previous_line,
+                delta_start,
+                end.1 - character,
+                token_type,
+                0,
+            ]);
+            previous_line = line;
+            previous_start = character;
+        }
+        Ok(json!({ "data": data }))
+    }
+}
+
+fn initialize_result() -> Value {
+    json!({
+        "capabilities": {
+            "positionEncoding": "utf-16",
+            // Full synchronization.
+            "textDocumentSync": 1,
+            "hoverProvider": true,
+            "definitionProvider": true,
+            "documentSymbolProvider": true,
+            "semanticTokensProvider": {
+                "legend": { "tokenTypes": SEMANTIC_TOKEN_TYPES, "tokenModifiers": [] },
+                "full": true,
+            },
+        },
+        "serverInfo": { "name": "spuzc", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+/// Reads modules from the open documents, falling back to the disk for anything that isn't open.
+struct Workspace<'a> {
+    documents: &'a HashMap<String, Document>,
+}
+
+impl Loader for Workspace<'_> {
+    fn read(&mut self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.documents.iter().find(|(uri, _)| uri_path(uri) == path) {
+            Some((_, document)) => Ok(document.text.clone().into_bytes()),
+            None => fs::read(path),
+        }
+    }
+}
+
+/// Converts a `file` URI to a path. Other URIs, like those of unsaved documents, are used as
+/// they are so they still identify the document.
+fn uri_path(uri: &str) -> PathBuf {
+    match uri.strip_prefix("file://") {
+        Some(path) => PathBuf::from(percent_decode(path)),
+        None => PathBuf::from(uri),
+    }
+}
+
+fn path_uri(path: &str) -> String {
+    let mut uri = String::from("file://");
+    for b in path.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(b as char)
+            }
+            b => uri.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    uri
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(b) => {
+                decoded.push(b);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+enum Target<'a> {
+    Function(&'a FuncDecl, Span),
+    Type(Type, Span),
+    Integer(Span),
+}
+
+/// Finds what the node at `offset` refers to.
+fn target_at(module: &Module, offset: usize) -> Option<Target<'_>> {
+    let contains = |span: Span| span.start.offset <= offset && offset <= span.end.offset;
+    let decls = module.functions();
+
+    for decl in decls.clone() {
+        if contains(decl.name.span) {
+            return Some(Target::Function(decl, decl.name.span));
+        }
+        if contains(decl.return_type.span) {
+            return Some(Target::Type(decl.return_type.value, decl.return_type.span));
+        }
+
+        let expr = &decl.body.value.expr;
+        if contains(expr.span) {
+            return match &expr.value {
+                Expr::Integer(_) => Some(Target::Integer(expr.span)),
+                Expr::Call(name) => decls
+                    .clone()
+                    .find(|d| d.name.value == name.value)
+                    .map(|d| Target::Function(d, name.span)),
+                Expr::Error => None,
+            };
+        }
+    }
+    None
+}
+
+fn signature(decl: &FuncDecl) -> String {
+    format!("func {}(): {}", decl.name.value, decl.return_type.value)
+}
+
+fn end_of(document: &Document) -> Span {
+    let end = document.line_index().location(document.text.len());
+    Span::in_file(document.file(), end, end)
+}
+
+/// Converts a location to an LSP line and UTF-16 character offset.
+fn position(document: &Document, location: Location) -> (usize, usize) {
+    let index = document.line_index();
+    (
+        index.line(location.offset),
+        index.column(location.offset, ColumnUnit::Utf16),
+    )
+}
+
+fn range(document: &Document, span: Span) -> Value {
+    let (start_line, start_character) = position(document, span.start);
+    let (end_line, end_character) = position(document, span.end);
+    json!({
+        "start": { "line": start_line, "character": start_character },
+        "end": { "line": end_line, "character": end_character },
+    })
+}
+
+/// Converts an LSP line and UTF-16 character offset to a byte offset, clamping it to the line.
+fn offset(document: &Document, line: usize, character: usize) -> usize {
+    document
+        .line_index()
+        .offset(line, character, ColumnUnit::Utf16)
+        .unwrap_or(document.text.len())
+}
+
+fn offset_param(document: &Document, params: &Value) -> Result<usize, ResponseError> {
+    let position = &params["position"];
+    match (position["line"].as_u64(), position["character"].as_u64()) {
+        (Some(line), Some(character)) => Ok(offset(document, line as usize, character as usize)),
+        _ => Err(ResponseError::invalid_params("missing position")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URI: &str = "file:///test.spz";
+
+    /// Runs the server over a scripted session, returning every message it sent.
+    fn session(messages: &[Value]) -> Vec<Value> {
+        let mut input = Vec::new();
+        for message in messages {
+            write_message(&mut input, message).unwrap();
+        }
+        let mut output = Vec::new();
+        run(&input[..], &mut output).unwrap();
+
+        let mut replies = Vec::new();
+        let mut output = &output[..];
+        while let Some(message) = read_message(&mut output).unwrap() {
+            replies.push(message);
+        }
+        replies
+    }
+
+    fn request(id: u64, method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+    }
+
+    fn open(text: &str) -> Value {
+        open_uri(URI, text)
+    }
+
+    fn open_uri(uri: &str, text: &str) -> Value {
+        notification(
+            "textDocument/didOpen",
+            json!({ "textDocument": { "uri": uri, "languageId": "spuzzum", "version": 1, "text": text } }),
+        )
+    }
+
+    fn at(line: u32, character: u32) -> Value {
+        json!({ "textDocument": { "uri": URI }, "position": { "line": line, "character": character } })
+    }
+
+    fn document() -> Value {
+        json!({ "textDocument": { "uri": URI } })
+    }
+
+    fn range_json(start: (u32, u32), end: (u32, u32)) -> Value {
+        json!({
+            "start": { "line": start.0, "character": start.1 },
+            "end": { "line": end.0, "character": end.1 },
+        })
+    }
+
+    #[test]
+    pub fn lifecycle() {
+        let replies = session(&[
+            request(1, "initialize", json!({ "capabilities": {} })),
+            notification("initialized", json!({})),
+            request(2, "shutdown", Value::Null),
+            request(3, "textDocument/hover", at(0, 0)),
+            notification("exit", Value::Null),
+            request(4, "shutdown", Value::Null),
+        ]);
+
+        assert_eq!(replies.len(), 3);
+        assert_eq!(replies[0]["id"], 1);
+        assert_eq!(replies[0]["result"]["capabilities"]["hoverProvider"], true);
+        assert_eq!(
+            replies[1],
+            json!({ "jsonrpc": "2.0", "id": 2, "result": null })
+        );
+        assert_eq!(replies[2]["error"]["code"], INVALID_REQUEST);
+    }
+
+    #[test]
+    pub fn unknown_method() {
+        let replies = session(&[request(1, "workspace/frobnicate", Value::Null)]);
+        assert_eq!(replies[0]["error"]["code"], METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    pub fn invalid_json() {
+        let mut input = b"Content-Length: 3\r\n\r\n{{{".to_vec();
+        write_message(&mut input, &request(1, "shutdown", Value::Null)).unwrap();
+        let mut output = Vec::new();
+        run(&input[..], &mut output).unwrap();
+
+        let mut output = &output[..];
+        let error = read_message(&mut output).unwrap().unwrap();
+        assert_eq!(error["error"]["code"], PARSE_ERROR);
+        assert_eq!(error["id"], Value::Null);
+        let response = read_message(&mut output).unwrap().unwrap();
+        assert_eq!(response["id"], 1);
+    }
+
+    #[test]
+    pub fn diagnostics() {
+        let replies = session(&[
+            open("func main(): int {\n  nope()\n}"),
+            notification(
+                "textDocument/didChange",
+                json!({
+                    "textDocument": { "uri": URI, "version": 2 },
+                    "contentChanges": [{ "text": "func main(): int { 42 }" }],
+                }),
+            ),
+            notification("textDocument/didClose", document()),
+        ]);
+
+        assert_eq!(replies.len(), 3);
+        assert_eq!(replies[0]["method"], "textDocument/publishDiagnostics");
+        assert_eq!(
+            replies[0]["params"]["diagnostics"],
+            json!([{
+                "range": range_json((1, 2), (1, 6)),
+                "severity": 1,
+                "source": "spuzc",
+                "message": "unknown function 'nope'",
+                "relatedInformation": [],
+            }])
+        );
+        assert_eq!(replies[1]["params"]["diagnostics"], json!([]));
+        assert_eq!(replies[2]["params"]["diagnostics"], json!([]));
+    }
+
+    #[test]
+    pub fn diagnostics_related_information() {
+        let replies = session(&[open("func a(): int { 1 }\nfunc a(): int { 2 }")]);
+        let diagnostic = &replies[0]["params"]["diagnostics"][0];
+        assert_eq!(diagnostic["range"], range_json((1, 5), (1, 6)));
+        assert_eq!(
+            diagnostic["relatedInformation"],
+            json!([{
+                "location": { "uri": URI, "range": range_json((0, 5), (0, 6)) },
+                "message": "previously defined here",
+            }])
+        );
+    }
+
+    #[test]
+    pub fn diagnostics_with_imports() {
+        let util = "file:///lib%20dir/util.spz";
+        let replies = session(&[
+            open_uri(
+                util,
+                "pub func answer(): int { 42 }\nfunc hidden(): int { 0 }",
+            ),
+            open_uri(
+                "file:///lib%20dir/main.spz",
+                "import util;\nfunc main(): int { answer() }",
+            ),
+            open_uri(
+                "file:///lib%20dir/other.spz",
+                "import util;\nfunc main(): int { hidden() }",
+            ),
+        ]);
+
+        assert_eq!(replies[1]["params"]["diagnostics"], json!([]));
+        let diagnostic = &replies[2]["params"]["diagnostics"][0];
+        assert_eq!(
+            diagnostic["message"],
+            "function 'hidden' is private to module 'util'"
+        );
+        assert_eq!(
+            diagnostic["relatedInformation"][0]["location"],
+            json!({ "uri": util, "range": range_json((1, 5), (1, 11)) })
+        );
+    }
+
+    #[test]
+    pub fn diagnostics_missing_import() {
+        let replies = session(&[open("import nope.at.all;\nfunc main(): int { 0 }")]);
+        let diagnostic = &replies[0]["params"]["diagnostics"][0];
+        assert!(diagnostic["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("cannot read '/nope/at/all.spz'"));
+        assert_eq!(diagnostic["range"], range_json((0, 0), (0, 19)));
+    }
+
+    #[test]
+    pub fn uri_conversions() {
+        assert_eq!(uri_path("file:///a%20b/c.spz"), Path::new("/a b/c.spz"));
+        assert_eq!(
+            uri_path("untitled:Untitled-1"),
+            Path::new("untitled:Untitled-1")
+        );
+        assert_eq!(path_uri("/a b/✨.spz"), "file:///a%20b/%E2%9C%A8.spz");
+        assert_eq!(uri_path(&path_uri("/a b/✨.spz")), Path::new("/a b/✨.spz"));
+    }
+
+    #[test]
+    pub fn diagnostics_syntax_errors() {
+        let replies = session(&[open("func main(): int {\n  ✨")]);
+        let diagnostics = &replies[0]["params"]["diagnostics"];
+        assert_eq!(diagnostics[0]["message"], "'{' is never closed");
+        assert_eq!(diagnostics[0]["range"], range_json((0, 17), (0, 18)));
+        assert_eq!(diagnostics[1]["message"], "unexpected character '✨'");
+        assert_eq!(diagnostics[1]["range"], range_json((1, 2), (1, 3)));
+    }
+
+    #[test]
+    pub fn hover() {
+        let source = "func answer(): int { 42 }\nfunc main(): int { answer() }";
+        let replies = session(&[
+            open(source),
+            request(1, "textDocument/hover", at(1, 21)),
+            request(2, "textDocument/hover", at(0, 21)),
+            request(3, "textDocument/hover", at(0, 16)),
+            request(4, "textDocument/hover", at(0, 13)),
+        ]);
+
+        assert_eq!(
+            replies[1]["result"],
+            json!({
+                "contents": { "kind": "markdown", "value": "```spuzzum\nfunc answer(): int\n```" },
+                "range": range_json((1, 19), (1, 25)),
+            })
+        );
+        assert_eq!(
+            replies[2]["result"]["contents"]["value"],
+            "```spuzzum\nint\n```"
+        );
+        assert_eq!(replies[3]["result"]["range"], range_json((0, 15), (0, 18)));
+        assert_eq!(replies[4]["result"], Value::Null);
+    }
+
+    #[test]
+    pub fn definition() {
+        let source = "func main(): int { answer() }\nfunc answer(): int { 42 }";
+        let replies = session(&[
+            open(source),
+            request(1, "textDocument/definition", at(0, 20)),
+            request(2, "textDocument/definition", at(0, 10)),
+        ]);
+
+        assert_eq!(
+            replies[1]["result"],
+            json!({ "uri": URI, "range": range_json((1, 5), (1, 11)) })
+        );
+        assert_eq!(replies[2]["result"], Value::Null);
+    }
+
+    #[test]
+    pub fn document_symbols() {
+        let source = "func main(): int {\n  42\n}";
+        let replies = session(&[
+            open(source),
+            request(1, "textDocument/documentSymbol", document()),
+        ]);
+
+        assert_eq!(
+            replies[1]["result"],
+            json!([{
+                "name": "main",
+                "detail": "(): int",
+                "kind": 12,
+                "range": range_json((0, 0), (2, 1)),
+                "selectionRange": range_json((0, 5), (0, 9)),
+            }])
+        );
+    }
+
+    #[test]
+    pub fn semantic_tokens() {
+        let source = "func 𝒜(): int { 𝒜() }";
+        let replies = session(&[
+            open("func main(): int {\n  answer()\n}"),
+            request(1, "textDocument/semanticTokens/full", document()),
+        ]);
+        assert_eq!(
+            replies[1]["result"]["data"],
+            json!([0, 0, 4, 0, 0, 0, 5, 4, 1, 0, 0, 8, 3, 2, 0, 1, 2, 6, 1, 0])
+        );
+
+        // Lengths and columns are in UTF-16 code units.
+        let replies = session(&[
+            open(source),
+            request(1, "textDocument/semanticTokens/full", document()),
+        ]);
+        assert_eq!(
+            replies[1]["result"]["data"],
+            json!([0, 0, 4, 0, 0, 0, 5, 2, 1, 0, 0, 6, 3, 2, 0, 0, 6, 2, 1, 0])
+        );
+    }
+
+    #[test]
+    pub fn unknown_document() {
+        let replies = session(&[request(1, "textDocument/documentSymbol", document())]);
+        assert_eq!(replies[0]["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[test]
+    pub fn position_conversions() {
+        let doc = Document::new("a\n𝒜b✨c");
+        // 𝒜 is two UTF-16 code units, ✨ is one.
+        assert_eq!(position(&doc, (2, 1, 0).into()), (1, 0));
+        assert_eq!(position(&doc, (6, 1, 1).into()), (1, 2));
+        assert_eq!(position(&doc, (10, 1, 3).into()), (1, 4));
+        assert_eq!(offset(&doc, 1, 0), 2);
+        assert_eq!(offset(&doc, 1, 2), 6);
+        assert_eq!(offset(&doc, 1, 4), 10);
+        assert_eq!(offset(&doc, 0, 99), 1);
+        assert_eq!(offset(&doc, 5, 0), doc.text.len());
+    }
+}
@@ -0,0 +1,122 @@
+//! Base protocol framing: a `Content-Length` header, a blank line, then a JSON body.
+
+use std::io::{self, BufRead, Write};
+
+use serde_json::Value;
+
+#[derive(Debug)]
+pub enum TransportError {
+    Io(io::Error),
+    MissingContentLength,
+    InvalidHeader(String),
+    InvalidJson(serde_json::Error),
+}
+
+impl From<io::Error> for TransportError {
+    fn from(e: io::Error) -> TransportError {
+        TransportError::Io(e)
+    }
+}
+
+impl std::fmt::Display for TransportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TransportError::Io(e) => write!(f, "{}", e),
+            TransportError::MissingContentLength => write!(f, "message has no Content-Length"),
+            TransportError::InvalidHeader(h) => write!(f, "invalid header '{}'", h),
+            TransportError::InvalidJson(e) => write!(f, "invalid JSON: {}", e),
+        }
+    }
+}
+
+/// Reads the next message, or `None` if the input ended cleanly between messages.
+pub fn read_message<R: BufRead>(input: &mut R) -> Result<Option<Value>, TransportError> {
+    let mut content_length = None;
+    let mut seen_header = false;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            if seen_header {
+                return Err(TransportError::Io(io::ErrorKind::UnexpectedEof.into()));
+            }
+            return Ok(None);
+        }
+        seen_header = true;
+
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            break;
+        }
+        match line.split_once(':') {
+            Some((name, value)) if name.eq_ignore_ascii_case("Content-Length") => {
+                let length = value
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| TransportError::InvalidHeader(line.to_owned()))?;
+                content_length = Some(length);
+            }
+            // Other headers, like Content-Type, don't change how we read the body.
+            Some(_) => {}
+            None => return Err(TransportError::InvalidHeader(line.to_owned())),
+        }
+    }
+
+    let length = content_length.ok_or(TransportError::MissingContentLength)?;
+    let mut body = vec![0u8; length];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(TransportError::InvalidJson)
+}
+
+pub fn write_message<W: Write>(output: &mut W, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    pub fn round_trip() {
+        let mut buf = Vec::new();
+        write_message(&mut buf, &json!({"jsonrpc": "2.0", "method": "✨"})).unwrap();
+        write_message(&mut buf, &json!({"id": 1})).unwrap();
+
+        let mut input = &buf[..];
+        assert_eq!(
+            read_message(&mut input).unwrap(),
+            Some(json!({"jsonrpc": "2.0", "method": "✨"}))
+        );
+        assert_eq!(read_message(&mut input).unwrap(), Some(json!({"id": 1})));
+        assert_eq!(read_message(&mut input).unwrap(), None);
+    }
+
+    #[test]
+    pub fn extra_headers() {
+        let mut input =
+            &b"Content-Type: application/vscode-jsonrpc; charset=utf-8\r\ncontent-length: 2\r\n\r\n{}"[..];
+        assert_eq!(read_message(&mut input).unwrap(), Some(json!({})));
+    }
+
+    #[test]
+    pub fn missing_content_length() {
+        let mut input = &b"Content-Type: text/plain\r\n\r\n{}"[..];
+        match read_message(&mut input) {
+            Err(TransportError::MissingContentLength) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    pub fn truncated_body() {
+        let mut input = &b"Content-Length: 10\r\n\r\n{}"[..];
+        match read_message(&mut input) {
+            Err(TransportError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+}
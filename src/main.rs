@@ -1 +1,225 @@
-fn main() {}
+use std::{fs, path::Path, process};
+
+use spuzc::{
+    bytecode::{disasm, format, Program},
+    diagnostics::Diagnostic,
+    formatter, lsp,
+    modules::{self, FileLoader},
+    parser,
+    repl::Repl,
+    sexpr,
+    text::{Document, SourceDatabase},
+    tokens,
+};
+
+const USAGE: &str = "usage: spuzc <command> [<args>]
+
+commands:
+    run <file>                  run a .spz or .spzb program, exiting with its result
+    compile <file> [-o <out>]   compile a .spz program to .spzb bytecode
+    disasm <file>               print the bytecode for a .spz or .spzb program
+    lex <file>                  print the tokens in a .spz file with their spans
+    parse [--emit=<what>] <file>
+                                print a .spz file's syntax tree as S-expressions with
+                                --emit=ast, the default, or as source with --emit=source
+    check <file>                report every error in a .spz program
+    fmt [--check] <file>...     format .spz files in place, or with --check list those that
+                                would change and exit with 1 if there are any
+    repl                        start an interactive session
+    lsp                         start a language server on stdin and stdout";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("run") => run(&args[1..]),
+        Some("compile") => compile(&args[1..]),
+        Some("disasm") => disasm(&args[1..]),
+        Some("lex") => lex(&args[1..]),
+        Some("parse") => parse(&args[1..]),
+        Some("check") => check(&args[1..]),
+        Some("fmt") => fmt(&args[1..]),
+        Some("repl") => repl(&args[1..]),
+        Some("lsp") => lsp(&args[1..]),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    match result {
+        Ok(code) => process::exit(code),
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<i32, String> {
+    let path = single_path(args)?;
+    let program = load(path)?;
+    modules::run(&program, path).map_err(|e| render(&[e], &SourceDatabase::new()))
+}
+
+fn compile(args: &[String]) -> Result<i32, String> {
+    let (path, output) = match args {
+        [path] => (
+            Path::new(path),
+            Path::new(path).with_extension(format::EXTENSION),
+        ),
+        [path, flag, output] if flag == "-o" => (Path::new(path), Path::new(output).to_owned()),
+        _ => return Err(USAGE.to_owned()),
+    };
+
+    let program = compile_source(path)?;
+    let file = fs::File::create(&output).map_err(|e| failure(&output, e))?;
+    program.write(file).map_err(|e| failure(&output, e))?;
+    Ok(0)
+}
+
+fn disasm(args: &[String]) -> Result<i32, String> {
+    let program = load(single_path(args)?)?;
+    print!("{}", disasm::disassemble(&program));
+    Ok(0)
+}
+
+fn lex(args: &[String]) -> Result<i32, String> {
+    let path = single_path(args)?;
+    let bytes = fs::read(path).map_err(|e| failure(path, e))?;
+    let tokens = tokens::tokenize(&Document::from_bytes(&bytes));
+    print!("{}", tokens::dump(&tokens));
+    Ok(if tokens.iter().all(Result::is_ok) {
+        0
+    } else {
+        1
+    })
+}
+
+fn parse(args: &[String]) -> Result<i32, String> {
+    let (emit, path) = match args {
+        [path] => ("ast", Path::new(path)),
+        [flag, path] => match flag.strip_prefix("--emit=") {
+            Some(emit @ ("ast" | "source")) => (emit, Path::new(path)),
+            _ => return Err(USAGE.to_owned()),
+        },
+        _ => return Err(USAGE.to_owned()),
+    };
+
+    let bytes = fs::read(path).map_err(|e| failure(path, e))?;
+    let mut sources = SourceDatabase::new();
+    let file = sources.add_bytes(path.display().to_string(), &bytes);
+    let document = sources.document(file).expect("file was just added");
+    let (module, errors) = parser::parse_recovering(document);
+    let rendered: Vec<_> = errors
+        .iter()
+        .map(|e| Diagnostic::from(e).render(&sources))
+        .collect();
+
+    // The AST is still worth seeing with errors in it, but it can't be printed as source.
+    if emit == "ast" {
+        print!("{}", sexpr::dump(&module, true));
+    }
+    if !errors.is_empty() {
+        return Err(rendered.join("\n\n"));
+    }
+    if emit == "source" {
+        print!("{}", formatter::print(&module));
+    }
+    Ok(0)
+}
+
+fn check(args: &[String]) -> Result<i32, String> {
+    let path = single_path(args)?;
+    let mut sources = SourceDatabase::new();
+    let mut warnings = Vec::new();
+    let result = modules::check(&mut sources, &mut FileLoader, path, &mut warnings);
+    if !warnings.is_empty() {
+        eprintln!("{}\n", render(&warnings, &sources));
+    }
+    result.map_err(|errors| render(&errors, &sources))?;
+    Ok(0)
+}
+
+fn fmt(args: &[String]) -> Result<i32, String> {
+    let (check, paths) = match args {
+        [flag, paths @ ..] if flag == "--check" => (true, paths),
+        paths => (false, paths),
+    };
+    if paths.is_empty() {
+        return Err(USAGE.to_owned());
+    }
+
+    let mut unformatted = false;
+    for path in paths.iter().map(Path::new) {
+        let bytes = fs::read(path).map_err(|e| failure(path, e))?;
+        let mut sources = SourceDatabase::new();
+        let file = sources.add_bytes(path.display().to_string(), &bytes);
+        let document = sources.document(file).expect("file was just added");
+        let formatted =
+            formatter::format(document).map_err(|e| Diagnostic::from(&e).render(&sources))?;
+        if formatted == document.text {
+            continue;
+        }
+
+        if check {
+            println!("{}", path.display());
+            unformatted = true;
+        } else {
+            let bom = if document.has_bom() { "\u{FEFF}" } else { "" };
+            fs::write(path, [bom, &formatted].concat()).map_err(|e| failure(path, e))?;
+        }
+    }
+    Ok(if unformatted { 1 } else { 0 })
+}
+
+fn repl(args: &[String]) -> Result<i32, String> {
+    if !args.is_empty() {
+        return Err(USAGE.to_owned());
+    }
+    let stdin = std::io::stdin();
+    Repl::new()
+        .run(stdin.lock(), std::io::stdout())
+        .map_err(|e| format!("error: {}", e))?;
+    Ok(0)
+}
+
+fn lsp(args: &[String]) -> Result<i32, String> {
+    if !args.is_empty() {
+        return Err(USAGE.to_owned());
+    }
+    let stdin = std::io::stdin();
+    lsp::run(stdin.lock(), std::io::stdout()).map_err(|e| format!("error: {}", e))?;
+    Ok(0)
+}
+
+fn single_path(args: &[String]) -> Result<&Path, String> {
+    match args {
+        [path] => Ok(Path::new(path)),
+        _ => Err(USAGE.to_owned()),
+    }
+}
+
+/// Loads a program, compiling it first unless it's already a `.spzb` file.
+fn load(path: &Path) -> Result<Program, String> {
+    if path.extension().is_some_and(|e| e == format::EXTENSION) {
+        let file = fs::File::open(path).map_err(|e| failure(path, e))?;
+        Program::read(file).map_err(|e| failure(path, e))
+    } else {
+        compile_source(path)
+    }
+}
+
+fn compile_source(path: &Path) -> Result<Program, String> {
+    let mut sources = SourceDatabase::new();
+    modules::compile(&mut sources, &mut FileLoader, path)
+        .map_err(|errors| render(&errors, &sources))
+}
+
+fn render(diagnostics: &[Diagnostic], sources: &SourceDatabase) -> String {
+    let rendered: Vec<_> = diagnostics.iter().map(|d| d.render(sources)).collect();
+    rendered.join("\n\n")
+}
+
+fn failure<E: std::fmt::Display>(path: &Path, e: E) -> String {
+    format!("error: {}: {}", path.display(), e)
+}
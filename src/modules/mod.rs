@@ -0,0 +1,629 @@
+//! Programs spread over several files
+//!
+//! A program starts at a root module, which imports others with `import` declarations. Loading
+//! reads every module in the graph into a `SourceDatabase`, and linking combines them into one
+//! `Module` the compiler can work with. Functions from modules other than the root are renamed
+//! to `module.function` along the way, so private functions with the same name in different
+//! modules don't collide.
+
+mod resolver;
+
+pub use resolver::{resolve, FileLoader, Loader, EXTENSION};
+
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    ast::{Expr, FuncDecl, Item, Module, Visibility},
+    bytecode::{
+        compiler,
+        vm::{self, Vm},
+        Program,
+    },
+    diagnostics::Diagnostic,
+    lints,
+    parser::{self, ParserError},
+    symbol::Symbol,
+    text::{FileId, SourceDatabase, Span, Spanned},
+};
+
+#[derive(Debug)]
+pub enum ModuleError {
+    Io {
+        path: PathBuf,
+        /// The import that referred to the file, unless it's the root.
+        import: Option<Span>,
+        error: io::Error,
+    },
+    Parse(Box<ParserError>),
+    /// A `module` declaration that doesn't match the name of its file.
+    NameMismatch {
+        declared: Spanned<Symbol>,
+        expected: String,
+    },
+    /// A module imports itself, directly or through other modules. `imports[i]` is where
+    /// `modules[i]` imports `modules[i + 1]`, and the last module is the first one again.
+    Cycle {
+        modules: Vec<String>,
+        imports: Vec<Span>,
+    },
+    UnknownFunction(Spanned<Symbol>),
+    Private {
+        name: Box<Spanned<Symbol>>,
+        module: String,
+        definition: Span,
+    },
+    Ambiguous {
+        name: Spanned<Symbol>,
+        modules: Vec<String>,
+    },
+}
+
+impl std::fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ModuleError::Io { path, error, .. } => {
+                write!(f, "cannot read '{}': {}", path.display(), error)
+            }
+            ModuleError::Parse(e) => write!(f, "{}", e),
+            ModuleError::NameMismatch { declared, expected } => write!(
+                f,
+                "module '{}' is declared in a file for module '{}'",
+                declared.value, expected
+            ),
+            ModuleError::Cycle { modules, .. } => {
+                write!(f, "import cycle: {}", modules.join(" -> "))
+            }
+            ModuleError::UnknownFunction(name) => write!(f, "unknown function '{}'", name.value),
+            ModuleError::Private { name, module, .. } => write!(
+                f,
+                "function '{}' is private to module '{}'",
+                name.value, module
+            ),
+            ModuleError::Ambiguous { name, modules } => write!(
+                f,
+                "function '{}' is imported from both '{}'",
+                name.value,
+                modules.join("' and '")
+            ),
+        }
+    }
+}
+
+impl From<&ModuleError> for Diagnostic {
+    fn from(e: &ModuleError) -> Diagnostic {
+        match e {
+            ModuleError::Io { import, .. } => Diagnostic::error(e.to_string(), *import),
+            ModuleError::Parse(e) => Diagnostic::from(&**e),
+            ModuleError::NameMismatch { declared, .. } => {
+                Diagnostic::error(e.to_string(), Some(declared.span))
+            }
+            ModuleError::Cycle { modules, imports } => {
+                // The error goes on the import that closes the cycle, with the rest as notes.
+                let (last, rest) = imports.split_last().expect("a cycle has an import");
+                rest.iter().enumerate().fold(
+                    Diagnostic::error(e.to_string(), Some(*last)),
+                    |diagnostic, (i, span)| {
+                        diagnostic.with_note(
+                            *span,
+                            format!("'{}' imports '{}' here", modules[i], modules[i + 1]),
+                        )
+                    },
+                )
+            }
+            ModuleError::UnknownFunction(name) | ModuleError::Ambiguous { name, .. } => {
+                Diagnostic::error(e.to_string(), Some(name.span))
+            }
+            ModuleError::Private {
+                name, definition, ..
+            } => Diagnostic::error(e.to_string(), Some(name.span))
+                .with_note(*definition, "defined here without 'pub'"),
+        }
+    }
+}
+
+/// A module loaded from a file.
+pub struct SourceModule {
+    /// The path of the module from the root's directory, like `shapes.square`.
+    pub name: String,
+    pub path: PathBuf,
+    pub file: FileId,
+    pub ast: Module,
+    /// The modules `ast.imports` refer to, as indices into the graph.
+    pub imports: Vec<usize>,
+}
+
+impl SourceModule {
+    pub fn function(&self, name: Symbol) -> Option<&FuncDecl> {
+        self.ast.functions().find(|decl| decl.name.value == name)
+    }
+}
+
+/// Every module making up a program. The root is always first.
+pub struct ModuleGraph {
+    pub modules: Vec<SourceModule>,
+}
+
+/// Loads the module in `root` and everything it imports, adding their text to `sources`.
+///
+/// Syntax errors don't stop loading, so every one in the program is reported. Any other error
+/// is returned along with the syntax errors found before it.
+pub fn load<L: Loader>(
+    sources: &mut SourceDatabase,
+    loader: &mut L,
+    root: &Path,
+) -> Result<ModuleGraph, Vec<ModuleError>> {
+    let mut builder = GraphBuilder {
+        sources,
+        loader,
+        root_dir: root.parent().map(Path::to_path_buf).unwrap_or_default(),
+        modules: Vec::new(),
+        loaded: HashMap::new(),
+        stack: Vec::new(),
+        errors: Vec::new(),
+    };
+    if let Err(e) = builder.visit(root, None) {
+        builder.errors.push(e);
+    }
+    if !builder.errors.is_empty() {
+        return Err(builder.errors);
+    }
+    Ok(ModuleGraph {
+        modules: builder.modules,
+    })
+}
+
+/// Loads, links and compiles the program rooted at `path`.
+pub fn compile<L: Loader>(
+    sources: &mut SourceDatabase,
+    loader: &mut L,
+    path: &Path,
+) -> Result<Program, Vec<Diagnostic>> {
+    check(sources, loader, path, &mut Vec::new())
+}
+
+/// Compiles the program rooted at `path` like `compile`, adding the lint warnings for it to
+/// `warnings`. They're found once the program has loaded, so even if compiling fails later.
+pub fn check<L: Loader>(
+    sources: &mut SourceDatabase,
+    loader: &mut L,
+    path: &Path,
+    warnings: &mut Vec<Diagnostic>,
+) -> Result<Program, Vec<Diagnostic>> {
+    let graph = load(sources, loader, path)
+        .map_err(|errors| errors.iter().map(Diagnostic::from).collect::<Vec<_>>())?;
+    warnings.extend(graph.lint());
+    let module = graph.link().map_err(|e| vec![Diagnostic::from(&e)])?;
+    compiler::compile(&module).map_err(|e| vec![Diagnostic::from(&e)])
+}
+
+/// Runs `program`, which was loaded from `path`, returning the code a process running it exits
+/// with, or the error to report if it fails.
+pub fn run(program: &Program, path: &Path) -> Result<i32, Diagnostic> {
+    match Vm::new(program).run() {
+        Ok(result) => Ok(vm::exit_code(result)),
+        Err(e) => Err(Diagnostic::error(
+            format!("{}: {}", path.display(), e),
+            None,
+        )),
+    }
+}
+
+struct GraphBuilder<'a, L> {
+    sources: &'a mut SourceDatabase,
+    loader: &'a mut L,
+    root_dir: PathBuf,
+    modules: Vec<SourceModule>,
+    loaded: HashMap<PathBuf, usize>,
+    /// The modules being loaded, each with the import that led to it.
+    stack: Vec<(usize, Option<Span>)>,
+    /// Syntax errors found so far.
+    errors: Vec<ModuleError>,
+}
+
+impl<L: Loader> GraphBuilder<'_, L> {
+    fn visit(&mut self, path: &Path, import: Option<Span>) -> Result<usize, ModuleError> {
+        if let Some(&index) = self.loaded.get(path) {
+            return match self.stack.iter().position(|&(i, _)| i == index) {
+                Some(start) => Err(self.cycle(start, import)),
+                None => Ok(index),
+            };
+        }
+
+        let text = self.loader.read(path).map_err(|error| ModuleError::Io {
+            path: path.to_owned(),
+            import,
+            error,
+        })?;
+        let file = self.sources.add_bytes(path.display().to_string(), &text);
+        let document = self.sources.document(file).expect("file was just added");
+        let (ast, errors) = parser::parse_recovering(document);
+        self.errors
+            .extend(errors.into_iter().map(|e| ModuleError::Parse(Box::new(e))));
+
+        let name = self.module_name(path);
+        if let Some(declared) = &ast.name {
+            let expected = name.rsplit('.').next().unwrap_or_default();
+            if declared.value != expected {
+                return Err(ModuleError::NameMismatch {
+                    declared: declared.clone(),
+                    expected: expected.to_owned(),
+                });
+            }
+        }
+
+        let targets: Vec<_> = ast
+            .imports
+            .iter()
+            .map(|import| (resolve(path, &import.value), import.span))
+            .collect();
+        let index = self.modules.len();
+        self.modules.push(SourceModule {
+            name,
+            path: path.to_owned(),
+            file,
+            ast,
+            imports: Vec::new(),
+        });
+        self.loaded.insert(path.to_owned(), index);
+
+        self.stack.push((index, import));
+        let mut imports = Vec::new();
+        for (target, span) in targets {
+            imports.push(self.visit(&target, Some(span))?);
+        }
+        self.stack.pop();
+
+        self.modules[index].imports = imports;
+        Ok(index)
+    }
+
+    /// Describes the cycle formed by importing `stack[start]` again from the top of the stack.
+    fn cycle(&self, start: usize, import: Option<Span>) -> ModuleError {
+        let cycle = &self.stack[start..];
+        let modules = cycle
+            .iter()
+            .chain(&cycle[..1])
+            .map(|&(i, _)| self.modules[i].name.clone())
+            .collect();
+        let imports = cycle[1..]
+            .iter()
+            .filter_map(|&(_, span)| span)
+            .chain(import)
+            .collect();
+        ModuleError::Cycle { modules, imports }
+    }
+
+    fn module_name(&self, path: &Path) -> String {
+        let relative = path.strip_prefix(&self.root_dir).unwrap_or(path);
+        relative
+            .with_extension("")
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+impl ModuleGraph {
+    pub fn root(&self) -> &SourceModule {
+        &self.modules[0]
+    }
+
+    /// Runs the lints over every module.
+    pub fn lint(&self) -> Vec<Diagnostic> {
+        self.modules
+            .iter()
+            .flat_map(|module| lints::check(&module.ast))
+            .collect()
+    }
+
+    /// Combines every module into one, resolving each call to the function it refers to.
+    pub fn link(&self) -> Result<Module, ModuleError> {
+        let mut items = Vec::new();
+        for (index, module) in self.modules.iter().enumerate() {
+            for item in &module.ast.items {
+                let decl = match &item.value {
+                    Item::Func(decl) => decl,
+                    // Left for the compiler to report.
+                    Item::Error => {
+                        items.push(item.clone());
+                        continue;
+                    }
+                };
+                let mut decl = decl.clone();
+                decl.name.value = self.qualify(index, decl.name.value);
+                decl.body.value.expr = self.link_expr(index, &decl.body.value.expr)?;
+                items.push(Spanned::new(Item::Func(decl), item.span));
+            }
+        }
+        Ok(Module {
+            items,
+            ..Module::default()
+        })
+    }
+
+    /// Gets the name a function in `module` has once linked. The root's functions keep their
+    /// names, so its `main` is still the entry point.
+    fn qualify(&self, module: usize, name: Symbol) -> Symbol {
+        if module == 0 {
+            name
+        } else {
+            Symbol::intern(&format!("{}.{}", self.modules[module].name, name))
+        }
+    }
+
+    fn link_expr(&self, module: usize, expr: &Spanned<Expr>) -> Result<Spanned<Expr>, ModuleError> {
+        match &expr.value {
+            Expr::Integer(_) | Expr::Error => Ok(expr.clone()),
+            Expr::Call(name) => {
+                let target = self.resolve_call(module, name)?;
+                let callee = Spanned::new(self.qualify(target, name.value), name.span);
+                Ok(Spanned::new(Expr::Call(callee), expr.span))
+            }
+        }
+    }
+
+    /// Finds the module defining the function `name` refers to from within `module`. Functions
+    /// in the module itself take precedence over imported ones.
+    fn resolve_call(&self, module: usize, name: &Spanned<Symbol>) -> Result<usize, ModuleError> {
+        let source = &self.modules[module];
+        if source.function(name.value).is_some() {
+            return Ok(module);
+        }
+
+        let mut candidates: Vec<_> = source
+            .imports
+            .iter()
+            .copied()
+            .filter(|&i| {
+                self.modules[i]
+                    .function(name.value)
+                    .is_some_and(|f| f.visibility == Visibility::Public)
+            })
+            .collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        match candidates[..] {
+            [target] => Ok(target),
+            [] => {
+                let private = source.imports.iter().find_map(|&i| {
+                    self.modules[i]
+                        .function(name.value)
+                        .map(|f| (&self.modules[i].name, f.name.span))
+                });
+                Err(match private {
+                    Some((module, definition)) => ModuleError::Private {
+                        name: Box::new(name.clone()),
+                        module: module.clone(),
+                        definition,
+                    },
+                    None => ModuleError::UnknownFunction(name.clone()),
+                })
+            }
+            _ => Err(ModuleError::Ambiguous {
+                name: name.clone(),
+                modules: candidates
+                    .iter()
+                    .map(|&i| self.modules[i].name.clone())
+                    .collect(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::vm::Vm;
+
+    impl Loader for HashMap<PathBuf, String> {
+        fn read(&mut self, path: &Path) -> io::Result<Vec<u8>> {
+            self.get(path)
+                .map(|text| text.clone().into_bytes())
+                .ok_or_else(|| io::ErrorKind::NotFound.into())
+        }
+    }
+
+    fn files(files: &[(&str, &str)]) -> HashMap<PathBuf, String> {
+        files
+            .iter()
+            .map(|&(path, text)| (PathBuf::from(path), text.to_owned()))
+            .collect()
+    }
+
+    fn run(files: &[(&str, &str)]) -> Result<i128, String> {
+        let mut sources = SourceDatabase::new();
+        let program = compile(&mut sources, &mut self::files(files), Path::new("main.spz"))
+            .map_err(|errors| {
+                let rendered: Vec<_> = errors.iter().map(|d| d.render(&sources)).collect();
+                rendered.join("\n\n")
+            })?;
+        Ok(Vm::new(&program).run().unwrap())
+    }
+
+    #[test]
+    pub fn compile_imports() {
+        let result = run(&[
+            (
+                "main.spz",
+                "import util;\nimport shapes.square;\nfunc main(): int { side() }",
+            ),
+            ("util.spz", "module util;\npub func helper(): int { 7 }"),
+            (
+                "shapes/square.spz",
+                "import helpers;\npub func side(): int { value() }",
+            ),
+            (
+                "shapes/helpers.spz",
+                "pub func value(): int { secret() }\nfunc secret(): int { 4 }",
+            ),
+        ]);
+        assert_eq!(result, Ok(4));
+    }
+
+    #[test]
+    pub fn syntax_errors_in_every_module() {
+        let mut sources = SourceDatabase::new();
+        let mut loader = files(&[
+            (
+                "main.spz",
+                "import util;\nfunc main(): int { 1\nfunc other(): int { }",
+            ),
+            ("util.spz", "func helper() int { 7 }"),
+        ]);
+        let errors = match load(&mut sources, &mut loader, Path::new("main.spz")) {
+            Err(errors) => errors,
+            Ok(_) => panic!("the modules should have errors"),
+        };
+
+        let locations: Vec<_> = errors
+            .iter()
+            .map(|e| {
+                let span = Diagnostic::from(e).span.unwrap();
+                (sources.name(span.file).unwrap().to_owned(), span.start.line)
+            })
+            .collect();
+        assert_eq!(
+            locations,
+            vec![
+                ("main.spz".to_owned(), 1),
+                ("main.spz".to_owned(), 2),
+                ("util.spz".to_owned(), 0),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn load_graph() {
+        let mut sources = SourceDatabase::new();
+        let mut loader = files(&[
+            (
+                "dir/main.spz",
+                "import a;\nimport b;\nfunc main(): int { 0 }",
+            ),
+            ("dir/a.spz", "import b;"),
+            ("dir/b.spz", ""),
+        ]);
+        let graph = load(&mut sources, &mut loader, Path::new("dir/main.spz")).unwrap();
+
+        let names: Vec<_> = graph.modules.iter().map(|m| m.name.to_string()).collect();
+        assert_eq!(names, vec!["main", "a", "b"]);
+        // Modules imported twice are only loaded once.
+        assert_eq!(graph.root().imports, vec![1, 2]);
+        assert_eq!(graph.modules[1].imports, vec![2]);
+        assert_eq!(sources.len(), 3);
+        assert_eq!(sources.name(graph.modules[2].file), Some("dir/b.spz"));
+    }
+
+    #[test]
+    pub fn private_functions_keep_their_names() {
+        let result = run(&[
+            (
+                "main.spz",
+                "import a;\nfunc main(): int { get() }\nfunc helper(): int { 1 }",
+            ),
+            (
+                "a.spz",
+                "pub func get(): int { helper() }\nfunc helper(): int { 2 }",
+            ),
+        ]);
+        assert_eq!(result, Ok(2));
+    }
+
+    #[test]
+    pub fn import_cycle() {
+        let err = run(&[
+            ("main.spz", "import a;\nfunc main(): int { 0 }"),
+            ("a.spz", "import b;"),
+            ("b.spz", "\nimport a;"),
+        ])
+        .unwrap_err();
+
+        assert_eq!(
+            err,
+            "error: import cycle: a -> b -> a\n\
+             \x20--> b.spz:2:1\n\
+             \x20 |\n\
+             2 | import a;\n\
+             \x20 | ^^^^^^^^^\n\
+             note: 'a' imports 'b' here\n\
+             \x20--> a.spz:1:1\n\
+             \x20 |\n\
+             1 | import b;\n\
+             \x20 | ^^^^^^^^^"
+        );
+    }
+
+    #[test]
+    pub fn import_self() {
+        let err = run(&[("main.spz", "import main;\nfunc main(): int { 0 }")]).unwrap_err();
+        assert!(err.starts_with("error: import cycle: main -> main\n --> main.spz:1:1"));
+    }
+
+    #[test]
+    pub fn missing_module() {
+        let err = run(&[("main.spz", "import nope;\nfunc main(): int { 0 }")]).unwrap_err();
+        assert!(
+            err.starts_with("error: cannot read 'nope.spz': entity not found\n --> main.spz:1:1")
+        );
+    }
+
+    #[test]
+    pub fn private_function() {
+        let err = run(&[
+            ("main.spz", "import a;\nfunc main(): int { hidden() }"),
+            ("a.spz", "func hidden(): int { 1 }"),
+        ])
+        .unwrap_err();
+
+        assert_eq!(
+            err,
+            "error: function 'hidden' is private to module 'a'\n\
+             \x20--> main.spz:2:20\n\
+             \x20 |\n\
+             2 | func main(): int { hidden() }\n\
+             \x20 |                    ^^^^^^\n\
+             note: defined here without 'pub'\n\
+             \x20--> a.spz:1:6\n\
+             \x20 |\n\
+             1 | func hidden(): int { 1 }\n\
+             \x20 |      ^^^^^^"
+        );
+    }
+
+    #[test]
+    pub fn ambiguous_function() {
+        let err = run(&[
+            ("main.spz", "import a;\nimport b;\nfunc main(): int { f() }"),
+            ("a.spz", "pub func f(): int { 1 }"),
+            ("b.spz", "pub func f(): int { 2 }"),
+        ])
+        .unwrap_err();
+        assert!(err.starts_with("error: function 'f' is imported from both 'a' and 'b'"));
+    }
+
+    #[test]
+    pub fn unknown_function_in_import() {
+        let err = run(&[
+            ("main.spz", "import a;\nfunc main(): int { f() }"),
+            ("a.spz", "pub func f(): int { nope() }"),
+        ])
+        .unwrap_err();
+        assert!(err.starts_with("error: unknown function 'nope'\n --> a.spz:1:21"));
+    }
+
+    #[test]
+    pub fn declared_name_mismatch() {
+        let err = run(&[
+            ("main.spz", "import a;\nfunc main(): int { 0 }"),
+            ("a.spz", "module b;"),
+        ])
+        .unwrap_err();
+        assert!(err.starts_with("error: module 'b' is declared in a file for module 'a'"));
+    }
+}
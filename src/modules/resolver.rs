@@ -0,0 +1,63 @@
+//! Locating and reading the files modules live in
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::ast::Import;
+
+/// The extension of source files.
+pub const EXTENSION: &str = "spz";
+
+/// Reads the text of modules.
+pub trait Loader {
+    /// Reads the file at `path`, which should be UTF-8, though that's checked when it's lexed.
+    fn read(&mut self, path: &Path) -> io::Result<Vec<u8>>;
+}
+
+/// Reads modules from the file system.
+pub struct FileLoader;
+
+impl Loader for FileLoader {
+    fn read(&mut self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+}
+
+/// Finds the file `import` refers to from the module in `importer`.
+///
+/// Imports are relative to the directory of the importing file, with each segment of the path
+/// but the last naming a directory: `import shapes.square;` in `geometry/main.spz` refers to
+/// `geometry/shapes/square.spz`.
+pub fn resolve(importer: &Path, import: &Import) -> PathBuf {
+    let mut path = importer.parent().map(Path::to_path_buf).unwrap_or_default();
+    for segment in &import.path {
+        path.push(segment.value.to_string());
+    }
+    path.set_extension(EXTENSION);
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser, text::Document};
+
+    fn import(source: &str) -> Import {
+        let module = parser::parse(&Document::new(source)).unwrap();
+        module.imports[0].value.clone()
+    }
+
+    #[test]
+    pub fn resolve_relative_to_importer() {
+        assert_eq!(
+            resolve(Path::new("geometry/main.spz"), &import("import util;")),
+            Path::new("geometry/util.spz")
+        );
+        assert_eq!(
+            resolve(Path::new("main.spz"), &import("import shapes.square;")),
+            Path::new("shapes/square.spz")
+        );
+    }
+}
@@ -1,9 +1,873 @@
-use crate::{ast::Expr, tokens::TokenBuffer};
+use crate::{
+    ast::{Block, Expr, FuncDecl, Import, Item, Module, Token, TokenKind, Type, Visibility},
+    diagnostics::Diagnostic,
+    symbol::Symbol,
+    text::{Document, Span, Spanned, Window},
+    tokens::{TokenBuffer, TokenError},
+};
 
+/// What the parser was in the middle of when it found an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Context {
+    ModuleDecl,
+    Import,
+    Signature,
+    Body,
+    Call,
+}
+
+impl std::fmt::Display for Context {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Context::ModuleDecl => write!(f, "module declaration"),
+            Context::Import => write!(f, "import"),
+            Context::Signature => write!(f, "function signature"),
+            Context::Body => write!(f, "function body"),
+            Context::Call => write!(f, "call"),
+        }
+    }
+}
+
+#[derive(Debug)]
 pub enum ParserError {
-    EndOfFile,
+    /// Something other than one of `expected` came next. If nothing is expected, there should
+    /// have been nothing more at all.
+    Expected {
+        expected: Vec<TokenKind>,
+        found: Spanned<Token>,
+        context: Option<Context>,
+    },
+    /// The file ended at `at`, where one of `expected` should have come.
+    EndOfFile {
+        expected: Vec<TokenKind>,
+        at: Span,
+        context: Option<Context>,
+    },
+    /// The `{` at `open` is never closed.
+    UnclosedDelimiter {
+        open: Span,
+    },
+    Lexical(Spanned<TokenError>),
+}
+
+impl std::fmt::Display for ParserError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let context = match self {
+            ParserError::Expected {
+                expected, found, ..
+            } if expected.is_empty() => {
+                write!(f, "unexpected {}", describe(&found.value))?;
+                None
+            }
+            ParserError::Expected {
+                expected,
+                found,
+                context,
+            } => {
+                write!(
+                    f,
+                    "expected {}, found {}",
+                    one_of(expected),
+                    describe(&found.value)
+                )?;
+                *context
+            }
+            ParserError::EndOfFile {
+                expected, context, ..
+            } => {
+                write!(f, "expected {}, found end of file", one_of(expected))?;
+                *context
+            }
+            ParserError::UnclosedDelimiter { .. } => {
+                write!(f, "'{{' is never closed")?;
+                None
+            }
+            ParserError::Lexical(e) => {
+                write!(f, "{}", e.value)?;
+                None
+            }
+        };
+        match context {
+            Some(context) => write!(f, " while parsing {}", context),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Describes a token for an error message, including its text unless that's all its kind says.
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Identifier(_) | Token::Integer(_) => format!("{} '{}'", token.kind(), token),
+        _ => token.kind().to_string(),
+    }
+}
+
+/// Lists `kinds` as alternatives, like `'func' or 'pub'`.
+fn one_of(kinds: &[TokenKind]) -> String {
+    let names: Vec<_> = kinds.iter().map(TokenKind::to_string).collect();
+    match names.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+        None => "nothing".to_owned(),
+    }
+}
+
+impl ParserError {
+    /// Gets the span the error is about.
+    pub fn span(&self) -> Span {
+        match self {
+            ParserError::Expected { found, .. } => found.span,
+            ParserError::EndOfFile { at, .. } => *at,
+            ParserError::UnclosedDelimiter { open } => *open,
+            ParserError::Lexical(e) => e.span,
+        }
+    }
+}
+
+impl From<&ParserError> for Diagnostic {
+    fn from(e: &ParserError) -> Diagnostic {
+        Diagnostic::error(e.to_string(), Some(e.span()))
+    }
+}
+
+pub fn parse(document: &Document) -> Result<Module, ParserError> {
+    let (module, mut errors) = parse_recovering(document);
+    if errors.is_empty() {
+        Ok(module)
+    } else {
+        Err(errors.remove(0))
+    }
+}
+
+/// Parses a module, carrying on past syntax errors so they can all be reported at once, in the
+/// order they appear in the source. Whatever couldn't be parsed is left in the module as `Error`
+/// items and expressions.
+pub fn parse_recovering(document: &Document) -> (Module, Vec<ParserError>) {
+    let mut parser = Parser::new(document);
+    let module = parser.module();
+    // An unclosed `{` is only noticed after what follows it.
+    parser.errors.sort_by_key(|e| e.span().start.offset);
+    (module, parser.errors)
+}
+
+/// Parses a module, also returning the comments in it.
+pub fn parse_with_comments(
+    document: &Document,
+) -> Result<(Module, Vec<Spanned<String>>), ParserError> {
+    let mut parser = Parser::new(document);
+    let module = parser.module();
+    match parser
+        .errors
+        .into_iter()
+        .min_by_key(|e| e.span().start.offset)
+    {
+        Some(e) => Err(e),
+        None => Ok((module, parser.buffer.take_comments())),
+    }
+}
+
+/// Parses a single expression, which must make up the whole document.
+pub fn parse_expr(document: &Document) -> Result<Spanned<Expr>, ParserError> {
+    let mut parser = Parser::new(document);
+    let expr = parser.expr();
+    let end = match parser.peek() {
+        None => Ok(()),
+        Some(t) => Err(ParserError::Expected {
+            expected: Vec::new(),
+            found: t.clone(),
+            context: None,
+        }),
+    };
+    // Lexical errors are recorded rather than returned, and come before anything found after.
+    match parser
+        .errors
+        .into_iter()
+        .min_by_key(|e| e.span().start.offset)
+    {
+        Some(e) => Err(e),
+        None => expr.and_then(|expr| end.map(|_| expr)),
+    }
+}
+
+struct Parser<'a> {
+    buffer: TokenBuffer<'a>,
+    /// Errors the parser has recovered from.
+    errors: Vec<ParserError>,
+    /// The span of the last token taken.
+    last: Span,
+    /// Whether text the lexer couldn't make sense of was skipped since the last token taken.
+    after_invalid: bool,
+    context: Option<Context>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(document: &'a Document) -> Parser<'a> {
+        let window = Window::new(document);
+        Parser {
+            last: window.span(),
+            buffer: TokenBuffer::new(window),
+            errors: Vec::new(),
+            after_invalid: false,
+            context: None,
+        }
+    }
+
+    fn module(&mut self) -> Module {
+        let name = if self.next_is(TokenKind::Module) {
+            self.recover(|p| p.within(Context::ModuleDecl, Parser::module_decl))
+        } else {
+            None
+        };
+
+        let mut imports = Vec::new();
+        while self.next_is(TokenKind::Import) {
+            imports.extend(self.recover(|p| p.within(Context::Import, Parser::import)));
+        }
+
+        let mut items = Vec::new();
+        while let Some(token) = self.peek() {
+            let start = token.span;
+            let item = self
+                .recover(Parser::item)
+                .unwrap_or_else(|| Spanned::new(Item::Error, start.merge(self.last)));
+            items.push(item);
+        }
+        Module {
+            name,
+            imports,
+            items,
+        }
+    }
+
+    /// Runs `rule`, and if it fails, records the error and skips to where parsing can carry on.
+    fn recover<T>(&mut self, rule: impl FnOnce(&mut Self) -> Result<T, ParserError>) -> Option<T> {
+        match rule(self) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.report(e);
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    /// Runs `rule`, noting that errors in it happened while parsing `context`.
+    fn within<T>(
+        &mut self,
+        context: Context,
+        rule: impl FnOnce(&mut Self) -> Result<T, ParserError>,
+    ) -> Result<T, ParserError> {
+        let outer = self.context.replace(context);
+        let result = rule(self);
+        self.context = outer;
+        result
+    }
+
+    /// Records an error to carry on from. A token that's unexpected only because invalid text
+    /// was skipped before it isn't worth reporting on top of the lexical error.
+    fn report(&mut self, e: ParserError) {
+        let cascading = matches!(
+            e,
+            ParserError::Expected { .. } | ParserError::EndOfFile { .. }
+        );
+        if !(cascading && self.after_invalid) {
+            self.errors.push(e);
+        }
+    }
+
+    /// Skips past the next `;` or `}`, or up to the start of the next function.
+    fn synchronize(&mut self) {
+        while let Some(token) = self.peek() {
+            let item = matches!(token.value, Token::Func | Token::Pub);
+            let end = matches!(token.value, Token::Semicolon | Token::RBrace);
+            if item || self.take().is_none() || end {
+                break;
+            }
+        }
+    }
+
+    /// Skips up to the `}` closing a block, or the start of the next function if it's missing,
+    /// returning the span of what was skipped.
+    fn skip_block(&mut self) -> Option<Span> {
+        let mut skipped: Option<Span> = None;
+        while let Some(token) = self.peek() {
+            if matches!(token.value, Token::RBrace | Token::Func | Token::Pub) {
+                break;
+            }
+            match self.take() {
+                Some(token) => skipped = Some(skipped.map_or(token.span, |s| s.merge(token.span))),
+                None => break,
+            }
+        }
+        skipped
+    }
+
+    fn module_decl(&mut self) -> Result<Spanned<Symbol>, ParserError> {
+        self.expect(TokenKind::Module)?;
+        let name = self.identifier()?;
+        self.expect(TokenKind::Semicolon)?;
+        Ok(name)
+    }
+
+    fn import(&mut self) -> Result<Spanned<Import>, ParserError> {
+        let start = self.expect(TokenKind::Import)?;
+        let mut path = vec![self.identifier()?];
+        while self.next_is(TokenKind::Dot) {
+            self.expect(TokenKind::Dot)?;
+            path.push(self.identifier()?);
+        }
+        let end = self.expect(TokenKind::Semicolon)?;
+        Ok(Spanned::new(Import { path }, start.merge(end)))
+    }
+
+    fn item(&mut self) -> Result<Spanned<Item>, ParserError> {
+        if self.next_is(TokenKind::Func) || self.next_is(TokenKind::Pub) {
+            let decl = self.func_decl()?;
+            Ok(Spanned::new(Item::Func(decl.value), decl.span))
+        } else {
+            Err(self.expected(&[TokenKind::Func, TokenKind::Pub]))
+        }
+    }
+
+    fn func_decl(&mut self) -> Result<Spanned<FuncDecl>, ParserError> {
+        let (visibility, start, name, return_type) = self.within(Context::Signature, |p| {
+            let (visibility, start) = if p.next_is(TokenKind::Pub) {
+                let start = p.expect(TokenKind::Pub)?;
+                p.expect(TokenKind::Func)?;
+                (Visibility::Public, start)
+            } else {
+                (Visibility::Private, p.expect(TokenKind::Func)?)
+            };
+            let name = p.identifier()?;
+            p.expect(TokenKind::LParen)?;
+            p.expect(TokenKind::RParen)?;
+            p.expect(TokenKind::Colon)?;
+            let return_type = p.type_name()?;
+            Ok((visibility, start, name, return_type))
+        })?;
+        let body = self.within(Context::Body, Parser::block)?;
+        let span = start.merge(body.span);
+        Ok(Spanned::new(
+            FuncDecl {
+                visibility,
+                name,
+                return_type,
+                body,
+            },
+            span,
+        ))
+    }
+
+    fn type_name(&mut self) -> Result<Spanned<Type>, ParserError> {
+        let span = self.expect(TokenKind::Int)?;
+        Ok(Spanned::new(Type::Int, span))
+    }
+
+    fn block(&mut self) -> Result<Spanned<Block>, ParserError> {
+        let start = self.expect(TokenKind::LBrace)?;
+        let expr = match self.expr() {
+            Ok(expr) => expr,
+            Err(e) => {
+                // With nothing left before the next function, the body and its `}` are both
+                // missing, and only the unclosed `{` is worth reporting.
+                let missing = match self.peek() {
+                    Some(t) => matches!(t.value, Token::Func | Token::Pub),
+                    None => true,
+                };
+                if !missing {
+                    self.report(e);
+                }
+                let span = self.skip_block().unwrap_or_else(|| self.last.at_end());
+                Spanned::new(Expr::Error, span)
+            }
+        };
+
+        // Skip anything extra after the expression, as long as the block still looks closed.
+        match self.peek() {
+            Some(t) if !matches!(t.value, Token::RBrace | Token::Func | Token::Pub) => {
+                let e = self.expected(&[TokenKind::RBrace]);
+                self.report(e);
+                self.skip_block();
+            }
+            _ => {}
+        }
+        if !self.next_is(TokenKind::RBrace) {
+            return Err(ParserError::UnclosedDelimiter { open: start });
+        }
+        let end = self.expect(TokenKind::RBrace)?;
+        Ok(Spanned::new(Block { expr }, start.merge(end)))
+    }
+
+    fn expr(&mut self) -> Result<Spanned<Expr>, ParserError> {
+        if self.next_is(TokenKind::Integer) {
+            self.constant()
+        } else if self.next_is(TokenKind::Identifier) {
+            self.within(Context::Call, Parser::call)
+        } else {
+            Err(self.expected(&[TokenKind::Integer, TokenKind::Identifier]))
+        }
+    }
+
+    fn constant(&mut self) -> Result<Spanned<Expr>, ParserError> {
+        match self.take_kind(TokenKind::Integer) {
+            Some(Spanned {
+                value: Token::Integer(i),
+                span,
+            }) => Ok(Spanned::new(Expr::Integer(i), span)),
+            _ => Err(self.expected(&[TokenKind::Integer])),
+        }
+    }
+
+    fn call(&mut self) -> Result<Spanned<Expr>, ParserError> {
+        let callee = self.identifier()?;
+        self.expect(TokenKind::LParen)?;
+        let end = self.expect(TokenKind::RParen)?;
+        let span = callee.span.merge(end);
+        Ok(Spanned::new(Expr::Call(callee), span))
+    }
+
+    fn identifier(&mut self) -> Result<Spanned<Symbol>, ParserError> {
+        match self.take_kind(TokenKind::Identifier) {
+            Some(Spanned {
+                value: Token::Identifier(name),
+                span,
+            }) => Ok(Spanned::new(name, span)),
+            _ => Err(self.expected(&[TokenKind::Identifier])),
+        }
+    }
+
+    /// Gets the next token without taking it, or `None` at the end of the file. Lexical errors
+    /// on the way are recorded and skipped.
+    fn peek(&mut self) -> Option<&Spanned<Token>> {
+        loop {
+            match self.buffer.peek() {
+                Ok(_) => break,
+                Err(TokenError::EndOfFile) => return None,
+                Err(e) => {
+                    let span = self.buffer.pending();
+                    self.errors
+                        .push(ParserError::Lexical(Spanned::new(e, span)));
+                    self.after_invalid = true;
+                }
+            }
+        }
+        self.buffer.peek().ok()
+    }
+
+    /// Determines if the next token is of the given kind, without taking it.
+    fn next_is(&mut self, kind: TokenKind) -> bool {
+        matches!(self.peek(), Some(t) if t.value.kind() == kind)
+    }
+
+    fn take(&mut self) -> Option<Spanned<Token>> {
+        self.peek()?;
+        let token = self.buffer.take_token().ok()?;
+        self.last = token.span;
+        self.after_invalid = false;
+        Some(token)
+    }
+
+    /// Takes the next token if it's of the given kind.
+    fn take_kind(&mut self, kind: TokenKind) -> Option<Spanned<Token>> {
+        if self.next_is(kind) {
+            self.take()
+        } else {
+            None
+        }
+    }
+
+    /// Takes the next token if it's of the given kind, and otherwise leaves it for error
+    /// recovery.
+    fn expect(&mut self, kind: TokenKind) -> Result<Span, ParserError> {
+        match self.take_kind(kind) {
+            Some(token) => Ok(token.span),
+            None => Err(self.expected(&[kind])),
+        }
+    }
+
+    /// Describes the next token as not being one of `expected`.
+    fn expected(&mut self, expected: &[TokenKind]) -> ParserError {
+        let expected = expected.to_vec();
+        let context = self.context;
+        match self.peek() {
+            Some(found) => ParserError::Expected {
+                expected,
+                found: found.clone(),
+                context,
+            },
+            None => ParserError::EndOfFile {
+                expected,
+                at: self.last.at_end(),
+                context,
+            },
+        }
+    }
 }
 
-fn constant(buffer: &mut TokenBuffer) -> Result<Expr, ParserError> {
-    unimplemented!()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(start: (usize, usize, usize), end: (usize, usize, usize)) -> Span {
+        Span::new(start.into(), end.into())
+    }
+
+    #[test]
+    pub fn parse_exit_code() {
+        let doc = Document::new("func main(): int {\n    42\n}");
+        let module = parse(&doc).unwrap();
+
+        assert_eq!(
+            module,
+            Module {
+                name: None,
+                imports: Vec::new(),
+                items: vec![Spanned::new(
+                    Item::Func(FuncDecl {
+                        visibility: Visibility::Private,
+                        name: Spanned::new("main".into(), span((5, 0, 5), (9, 0, 9))),
+                        return_type: Spanned::new(Type::Int, span((13, 0, 13), (16, 0, 16))),
+                        body: Spanned::new(
+                            Block {
+                                expr: Spanned::new(Expr::Integer(42), span((23, 1, 4), (25, 1, 6))),
+                            },
+                            span((17, 0, 17), (27, 2, 1))
+                        ),
+                    }),
+                    span((0, 0, 0), (27, 2, 1))
+                )],
+            }
+        );
+    }
+
+    #[test]
+    pub fn parse_call() {
+        let doc = Document::new("func main(): int { answer() }");
+        let module = parse(&doc).unwrap();
+        let decl = module.functions().next().unwrap();
+
+        assert_eq!(
+            decl.body.value.expr,
+            Spanned::new(
+                Expr::Call(Spanned::new(
+                    "answer".into(),
+                    span((19, 0, 19), (25, 0, 25))
+                )),
+                span((19, 0, 19), (27, 0, 27))
+            )
+        );
+    }
+
+    #[test]
+    pub fn parse_multiple_items() {
+        let doc = Document::new("func a(): int { 1 }\nfunc b(): int { a() }");
+        let module = parse(&doc).unwrap();
+        assert_eq!(module.items.len(), 2);
+    }
+
+    #[test]
+    pub fn parse_module_header() {
+        let doc = Document::new("module geometry;\nimport util;\nimport shapes.square;\n");
+        let module = parse(&doc).unwrap();
+
+        assert_eq!(
+            module.name,
+            Some(Spanned::new(
+                "geometry".into(),
+                span((7, 0, 7), (15, 0, 15))
+            ))
+        );
+        assert_eq!(module.imports.len(), 2);
+        assert_eq!(module.imports[0].value.to_string(), "util");
+        assert_eq!(module.imports[1].value.to_string(), "shapes.square");
+        assert_eq!(module.imports[1].span, span((30, 2, 0), (51, 2, 21)));
+        assert!(module.items.is_empty());
+    }
+
+    #[test]
+    pub fn parse_visibility() {
+        let doc = Document::new("pub func a(): int { 1 }\nfunc b(): int { 2 }");
+        let module = parse(&doc).unwrap();
+        let visibilities: Vec<_> = module.functions().map(|decl| decl.visibility).collect();
+
+        assert_eq!(visibilities, vec![Visibility::Public, Visibility::Private]);
+        assert_eq!(module.items[0].span, span((0, 0, 0), (23, 0, 23)));
+    }
+
+    #[test]
+    pub fn parse_import_after_items() {
+        let doc = Document::new("func a(): int { 1 }\nimport util;");
+        match parse(&doc) {
+            Err(ParserError::Expected {
+                expected, found, ..
+            }) => {
+                assert_eq!(expected, vec![TokenKind::Func, TokenKind::Pub]);
+                assert_eq!(found.value, Token::Import);
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    pub fn parse_expression() {
+        let doc = Document::new(" answer() ");
+        assert_eq!(
+            parse_expr(&doc).unwrap(),
+            Spanned::new(
+                Expr::Call(Spanned::new("answer".into(), span((1, 0, 1), (7, 0, 7)))),
+                span((1, 0, 1), (9, 0, 9))
+            )
+        );
+    }
+
+    #[test]
+    pub fn parse_expression_trailing_tokens() {
+        let doc = Document::new("42 }");
+        match parse_expr(&doc) {
+            Err(ParserError::Expected {
+                expected, found, ..
+            }) => {
+                assert!(expected.is_empty());
+                assert_eq!(
+                    found,
+                    Spanned::new(Token::RBrace, span((3, 0, 3), (4, 0, 4)))
+                );
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    pub fn parse_empty() {
+        let doc = Document::new("  \n ");
+        let module = parse(&doc).unwrap();
+        assert!(module.items.is_empty());
+    }
+
+    #[test]
+    pub fn parse_unexpected_token() {
+        let doc = Document::new("func main() int { 42 }");
+        match parse(&doc) {
+            Err(ParserError::Expected {
+                expected,
+                found,
+                context,
+            }) => {
+                assert_eq!(expected, vec![TokenKind::Colon]);
+                assert_eq!(
+                    found,
+                    Spanned::new(Token::Int, span((12, 0, 12), (15, 0, 15)))
+                );
+                assert_eq!(context, Some(Context::Signature));
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    pub fn parse_missing_brace() {
+        let doc = Document::new("func main(): int { 42");
+        match parse(&doc) {
+            Err(ParserError::UnclosedDelimiter { open }) => {
+                assert_eq!(open, span((17, 0, 17), (18, 0, 18)))
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    pub fn parse_end_of_file() {
+        let doc = Document::new("func main(): int  ");
+        match parse(&doc) {
+            Err(ParserError::EndOfFile {
+                expected,
+                at,
+                context,
+            }) => {
+                assert_eq!(expected, vec![TokenKind::LBrace]);
+                assert_eq!(at, span((16, 0, 16), (16, 0, 16)));
+                assert_eq!(context, Some(Context::Body));
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    pub fn error_messages() {
+        let message = |source: &str| parse(&Document::new(source)).unwrap_err().to_string();
+        assert_eq!(
+            message("func main() int { 42 }"),
+            "expected ':', found 'int' while parsing function signature"
+        );
+        assert_eq!(
+            message("func main(): int { }"),
+            "expected integer or identifier, found '}' while parsing function body"
+        );
+        assert_eq!(
+            message("func main(): int { f( }"),
+            "expected ')', found '}' while parsing call"
+        );
+        assert_eq!(
+            message("import a.b"),
+            "expected ';', found end of file while parsing import"
+        );
+        assert_eq!(message("7"), "expected 'func' or 'pub', found integer '7'");
+        assert_eq!(
+            message("func 1"),
+            "expected identifier, found integer '1' while parsing function signature"
+        );
+        assert_eq!(
+            message("func f(): x"),
+            "expected 'int', found identifier 'x' while parsing function signature"
+        );
+        assert_eq!(message("func f(): int { 1"), "'{' is never closed");
+        assert_eq!(message("¿"), "unexpected character '¿'");
+    }
+
+    fn unexpected(errors: &[ParserError]) -> Vec<(Token, Span)> {
+        errors
+            .iter()
+            .map(|e| match e {
+                ParserError::Expected { found, .. } => (found.value.clone(), found.span),
+                e => panic!("unexpected error: {:?}", e),
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn recover_missing_brace() {
+        let doc = Document::new("func a(): int { 1\nfunc b(): int { 2 }");
+        let (module, errors) = parse_recovering(&doc);
+
+        match &errors[..] {
+            [ParserError::UnclosedDelimiter { open }] => {
+                assert_eq!(*open, span((14, 0, 14), (15, 0, 15)))
+            }
+            e => panic!("unexpected errors: {:?}", e),
+        }
+        assert_eq!(
+            module.items[0],
+            Spanned::new(Item::Error, span((0, 0, 0), (17, 0, 17)))
+        );
+        let names: Vec<_> = module
+            .functions()
+            .map(|d| d.name.value.to_string())
+            .collect();
+        assert_eq!(names, vec!["b"]);
+    }
+
+    #[test]
+    pub fn recover_missing_body() {
+        let doc = Document::new("func a(): int { 1 }\nfunc b(): int { \nfunc c(): int { 1 } x");
+        let (module, errors) = parse_recovering(&doc);
+
+        match &errors[..] {
+            [ParserError::UnclosedDelimiter { open }, ParserError::Expected { found, .. }] => {
+                assert_eq!(*open, span((34, 1, 14), (35, 1, 15)));
+                assert_eq!(found.value, Token::Identifier("x".into()));
+            }
+            e => panic!("unexpected errors: {:?}", e),
+        }
+        let names: Vec<_> = module
+            .functions()
+            .map(|d| d.name.value.to_string())
+            .collect();
+        assert_eq!(names, vec!["a", "c"]);
+    }
+
+    #[test]
+    pub fn recover_every_error() {
+        let doc = Document::new(
+            "func a() int { 1 }\nfunc b(): int { }\nfunc c(): int { 3 4 }\nfunc d(): int { d() }",
+        );
+        let (module, errors) = parse_recovering(&doc);
+
+        assert_eq!(
+            unexpected(&errors),
+            vec![
+                (Token::Int, span((9, 0, 9), (12, 0, 12))),
+                (Token::RBrace, span((35, 1, 16), (36, 1, 17))),
+                (Token::Integer(4), span((55, 2, 18), (56, 2, 19))),
+            ]
+        );
+        assert_eq!(module.items.len(), 4);
+        assert_eq!(module.items[0].value, Item::Error);
+
+        let exprs: Vec<_> = module
+            .functions()
+            .map(|d| d.body.value.expr.value.clone())
+            .collect();
+        assert_eq!(
+            exprs,
+            vec![
+                Expr::Error,
+                Expr::Integer(3),
+                Expr::Call(Spanned::new("d".into(), span((75, 3, 16), (76, 3, 17))))
+            ]
+        );
+        // The first error is the one `parse` reports.
+        match parse(&doc) {
+            Err(ParserError::Expected { found, .. }) => assert_eq!(found.value, Token::Int),
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    pub fn recover_in_header() {
+        let doc = Document::new("module ;\nimport a.;\nimport b;\nfunc f(): int { 1 }");
+        let (module, errors) = parse_recovering(&doc);
+
+        assert_eq!(
+            unexpected(&errors),
+            vec![
+                (Token::Semicolon, span((7, 0, 7), (8, 0, 8))),
+                (Token::Semicolon, span((18, 1, 9), (19, 1, 10))),
+            ]
+        );
+        assert_eq!(module.name, None);
+        let imports: Vec<_> = module.imports.iter().map(|i| i.value.to_string()).collect();
+        assert_eq!(imports, vec!["b"]);
+        assert_eq!(module.functions().count(), 1);
+    }
+
+    #[test]
+    pub fn recover_lexical_errors() {
+        let doc = Document::new("func a(): int { ✨ }\nfunc b(): int { 2 ¿ }");
+        let (module, errors) = parse_recovering(&doc);
+
+        let spans: Vec<_> = errors
+            .iter()
+            .map(|e| match e {
+                ParserError::Lexical(e) => e.span,
+                e => panic!("unexpected error: {:?}", e),
+            })
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                span((16, 0, 16), (19, 0, 17)),
+                span((40, 1, 18), (42, 1, 19))
+            ]
+        );
+        let exprs: Vec<_> = module
+            .functions()
+            .map(|d| d.body.value.expr.value.clone())
+            .collect();
+        assert_eq!(exprs, vec![Expr::Error, Expr::Integer(2)]);
+    }
+
+    #[test]
+    pub fn recover_from_anything() {
+        let sources = [
+            "}}}} ;; func",
+            "pub pub func ( ) : { } module x; import",
+            "func f(): int { func g(): int { 1 }",
+            "7 func f(): int { 1 } } 8",
+        ];
+        for source in sources.iter() {
+            let (module, errors) = parse_recovering(&Document::new(*source));
+            assert!(!errors.is_empty(), "{:?} should have errors", source);
+            assert!(!module.items.is_empty());
+        }
+    }
 }
@@ -0,0 +1,307 @@
+//! Interactive read-eval-print loop
+//!
+//! Each entry is either one or more `func` definitions, which stay in scope for later entries, or
+//! a single expression, which is compiled along with every definition so far and then run.
+
+use std::io::{self, BufRead, Write};
+
+use crate::{
+    ast::{Block, Expr, FuncDecl, Item, Module, Token, Type, Visibility},
+    bytecode::{compiler, vm::Vm},
+    diagnostics::Diagnostic,
+    parser,
+    symbol::{Session, Symbol},
+    text::{Document, SourceDatabase, Spanned, Window},
+    tokens::{TokenBuffer, TokenError},
+};
+
+pub const PROMPT: &str = "> ";
+pub const CONTINUATION_PROMPT: &str = ". ";
+
+/// The name of the function expressions are compiled into. It isn't a valid identifier, so it
+/// can't collide with anything the user defines.
+const EXPR_FUNCTION: &str = "<expr>";
+
+#[derive(Debug, PartialEq)]
+pub enum Output {
+    Defined(Vec<(String, Type)>),
+    Value(i128, Type),
+}
+
+impl std::fmt::Display for Output {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Output::Defined(functions) => {
+                for (i, (name, ty)) in functions.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "func {}(): {}", name, ty)?;
+                }
+                Ok(())
+            }
+            Output::Value(value, ty) => write!(f, "{}: {}", value, ty),
+        }
+    }
+}
+
+pub struct Repl {
+    definitions: Module,
+    /// Every entry so far, since definitions from earlier entries can show up in diagnostics.
+    sources: SourceDatabase,
+    /// Where the names in entries are interned, so they're freed along with the REPL.
+    session: Session,
+}
+
+impl Default for Repl {
+    fn default() -> Repl {
+        Repl::new()
+    }
+}
+
+impl Repl {
+    pub fn new() -> Repl {
+        Repl {
+            definitions: Module::default(),
+            sources: SourceDatabase::new(),
+            session: Session::new(),
+        }
+    }
+
+    pub fn sources(&self) -> &SourceDatabase {
+        &self.sources
+    }
+
+    /// Reads entries from `input` until it ends, writing results and errors to `output`.
+    pub fn run<R: BufRead, W: Write>(&mut self, mut input: R, mut output: W) -> io::Result<()> {
+        let mut pending = String::new();
+        loop {
+            if pending.is_empty() {
+                write!(output, "{}", PROMPT)?;
+            } else {
+                write!(output, "{}", CONTINUATION_PROMPT)?;
+            }
+            output.flush()?;
+
+            if input.read_line(&mut pending)? == 0 {
+                return Ok(());
+            }
+            if is_incomplete(&pending) {
+                continue;
+            }
+
+            let source = std::mem::take(&mut pending);
+            if source.trim().is_empty() {
+                continue;
+            }
+
+            match self.eval(source) {
+                Ok(result) => writeln!(output, "{}", result)?,
+                Err(diagnostic) => writeln!(output, "{}", diagnostic.render(&self.sources))?,
+            }
+        }
+    }
+
+    /// Evaluates a single entry. Any diagnostic refers to files in `sources`.
+    pub fn eval<S: Into<String>>(&mut self, source: S) -> Result<Output, Diagnostic> {
+        let session = self.session.clone();
+        session.enter(|| self.eval_in_session(source.into()))
+    }
+
+    fn eval_in_session(&mut self, source: String) -> Result<Output, Diagnostic> {
+        // Entries are numbered so diagnostics can tell them apart: `<repl-1>`, `<repl-2>`...
+        let name = format!("<repl-{}>", self.sources.len() + 1);
+        let file = self.sources.add(name, source);
+        let document = self.sources.document(file).expect("file was just added");
+
+        let mut buffer = TokenBuffer::new(Window::new(document));
+        let is_definition = match buffer.peek() {
+            Ok(t) => t.value == Token::Func || t.value == Token::Pub,
+            Err(_) => false,
+        };
+
+        if is_definition {
+            let module = parser::parse(document).map_err(|e| Diagnostic::from(&e))?;
+            self.define(module)
+        } else {
+            let expr = parser::parse_expr(document).map_err(|e| Diagnostic::from(&e))?;
+            self.evaluate(expr)
+        }
+    }
+
+    fn define(&mut self, module: Module) -> Result<Output, Diagnostic> {
+        let new_names: Vec<_> = module.functions().map(|decl| decl.name.value).collect();
+
+        // Redefining a function replaces the old definition.
+        let mut items: Vec<_> = self
+            .definitions
+            .items
+            .iter()
+            .filter(|item| {
+                !matches!(&item.value, Item::Func(decl) if new_names.contains(&decl.name.value))
+            })
+            .cloned()
+            .collect();
+        let defined = module
+            .functions()
+            .map(|decl| (decl.name.value.to_string(), decl.return_type.value))
+            .collect();
+        items.extend(module.items);
+        let candidate = Module {
+            items,
+            ..Module::default()
+        };
+
+        // Compiling checks the new definitions only refer to functions that exist.
+        if let Some(name) = new_names.first() {
+            compiler::compile_entry(&candidate, *name).map_err(|e| Diagnostic::from(&e))?;
+        }
+
+        self.definitions = candidate;
+        Ok(Output::Defined(defined))
+    }
+
+    fn evaluate(&self, expr: Spanned<Expr>) -> Result<Output, Diagnostic> {
+        let ty = self.type_of(&expr)?;
+        let span = expr.span;
+        let function = FuncDecl {
+            visibility: Visibility::Private,
+            name: Spanned::new(Symbol::intern(EXPR_FUNCTION), span),
+            return_type: Spanned::new(ty, span),
+            body: Spanned::new(Block { expr }, span),
+        };
+
+        let mut module = self.definitions.clone();
+        module.items.push(Spanned::new(Item::Func(function), span));
+
+        let program = compiler::compile_entry(&module, Symbol::intern(EXPR_FUNCTION))
+            .map_err(|e| Diagnostic::from(&e))?;
+        let value = Vm::new(&program)
+            .run()
+            .map_err(|e| Diagnostic::error(e.to_string(), Some(span)))?;
+        Ok(Output::Value(value, ty))
+    }
+
+    fn type_of(&self, expr: &Spanned<Expr>) -> Result<Type, Diagnostic> {
+        match &expr.value {
+            Expr::Integer(_) => Ok(Type::Int),
+            Expr::Call(name) => self
+                .definitions
+                .functions()
+                .find(|decl| decl.name.value == name.value)
+                .map(|decl| decl.return_type.value)
+                .ok_or_else(|| {
+                    Diagnostic::from(&compiler::CompileError::UnknownFunction(name.clone()))
+                }),
+            Expr::Error => Err(Diagnostic::from(&compiler::CompileError::Syntax(expr.span))),
+        }
+    }
+}
+
+/// Determines if `input` has unclosed braces, meaning the entry continues on the next line.
+pub fn is_incomplete(input: &str) -> bool {
+    let document = Document::new(input);
+    let mut buffer = TokenBuffer::new(Window::new(&document));
+    let mut depth = 0usize;
+    loop {
+        match buffer.take_token() {
+            Ok(t) if t.value == Token::LBrace => depth += 1,
+            Ok(t) if t.value == Token::RBrace => depth = depth.saturating_sub(1),
+            Ok(_) => {}
+            Err(TokenError::EndOfFile) => return depth > 0,
+            // Let evaluation report the error.
+            Err(_) => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(repl: &mut Repl, input: &str) -> Result<Output, Diagnostic> {
+        repl.eval(input)
+    }
+
+    #[test]
+    pub fn eval_expression() {
+        let mut repl = Repl::new();
+        assert_eq!(eval(&mut repl, "42"), Ok(Output::Value(42, Type::Int)));
+    }
+
+    #[test]
+    pub fn eval_definitions_stay_in_scope() {
+        let mut repl = Repl::new();
+        assert_eq!(
+            eval(&mut repl, "func answer(): int { 42 }"),
+            Ok(Output::Defined(vec![("answer".into(), Type::Int)]))
+        );
+        assert_eq!(
+            eval(&mut repl, "func outer(): int { answer() }"),
+            Ok(Output::Defined(vec![("outer".into(), Type::Int)]))
+        );
+        assert_eq!(eval(&mut repl, "outer()"), Ok(Output::Value(42, Type::Int)));
+    }
+
+    #[test]
+    pub fn eval_redefinition() {
+        let mut repl = Repl::new();
+        eval(&mut repl, "func answer(): int { 42 }").unwrap();
+        eval(&mut repl, "func answer(): int { 7 }").unwrap();
+        assert_eq!(eval(&mut repl, "answer()"), Ok(Output::Value(7, Type::Int)));
+    }
+
+    #[test]
+    pub fn eval_rejected_definition_is_discarded() {
+        let mut repl = Repl::new();
+        let err = eval(&mut repl, "func broken(): int { nope() }").unwrap_err();
+        assert_eq!(err.message, "unknown function 'nope'");
+
+        let err = eval(&mut repl, "broken()").unwrap_err();
+        assert_eq!(err.message, "unknown function 'broken'");
+    }
+
+    #[test]
+    pub fn eval_error_span() {
+        let mut repl = Repl::new();
+        eval(&mut repl, "42").unwrap();
+        let err = eval(&mut repl, "func f(): int {\n  g()\n}").unwrap_err();
+        assert_eq!(
+            err.render(repl.sources()),
+            "error: unknown function 'g'\n\
+             \x20--> <repl-2>:2:3\n\
+             \x20 |\n\
+             2 |   g()\n\
+             \x20 |   ^"
+        );
+    }
+
+    #[test]
+    pub fn incomplete_input() {
+        assert!(!is_incomplete(""));
+        assert!(!is_incomplete("42"));
+        assert!(is_incomplete("func main(): int {"));
+        assert!(is_incomplete("func main(): int {\n  {\n}"));
+        assert!(!is_incomplete("func main(): int {\n  42\n}"));
+        assert!(!is_incomplete("func main(): int { 42 } }"));
+    }
+
+    #[test]
+    pub fn run_session() {
+        let input = "func answer(): int {\n  42\n}\n\nanswer()\nnope()\n";
+        let mut output = Vec::new();
+        Repl::new().run(input.as_bytes(), &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "> . . func answer(): int\n\
+             > > 42: int\n\
+             > error: unknown function 'nope'\n\
+             \x20--> <repl-3>:1:1\n\
+             \x20 |\n\
+             1 | nope()\n\
+             \x20 | ^^^^\n\
+             > "
+        );
+    }
+}
@@ -0,0 +1,186 @@
+//! Dumping the AST as S-expressions
+//!
+//! Each node is a list of what it is, its value if it has one, its span, and then its parts, each
+//! indented on its own line:
+//!
+//! ```text
+//! (module 1:1-3:2
+//!   (func 1:1-3:2
+//!     (name main 1:6-1:10)
+//!     (type int 1:14-1:17)
+//!     (block 1:18-3:2
+//!       (integer 42 2:5-2:7))))
+//! ```
+
+use crate::{
+    ast::{Expr, Item, Module, Visibility},
+    text::{Span, Spanned},
+};
+
+/// Dumps `module`, leaving out spans unless `spans` is set.
+pub fn dump(module: &Module, spans: bool) -> String {
+    let mut out = String::new();
+    Node::module(module).write(&mut out, 0, spans);
+    out.push('\n');
+    out
+}
+
+struct Node {
+    head: String,
+    span: Option<Span>,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn new<S: Into<String>>(head: S, span: Option<Span>) -> Node {
+        Node {
+            head: head.into(),
+            span,
+            children: Vec::new(),
+        }
+    }
+
+    fn leaf<T: std::fmt::Display>(kind: &str, value: &Spanned<T>) -> Node {
+        Node::new(format!("{} {}", kind, value.value), Some(value.span))
+    }
+
+    fn module(module: &Module) -> Node {
+        let spans = module
+            .name
+            .iter()
+            .map(|n| n.span)
+            .chain(module.imports.iter().map(|i| i.span))
+            .chain(module.items.iter().map(|i| i.span));
+        let span = spans.reduce(|a, b| a.merge(b));
+
+        let mut node = Node::new("module", span);
+        node.children
+            .extend(module.name.iter().map(|name| Node::leaf("name", name)));
+        node.children.extend(
+            module
+                .imports
+                .iter()
+                .map(|import| Node::leaf("import", import)),
+        );
+        node.children.extend(module.items.iter().map(Node::item));
+        node
+    }
+
+    fn item(item: &Spanned<Item>) -> Node {
+        let decl = match &item.value {
+            Item::Func(decl) => decl,
+            Item::Error => return Node::new("error", Some(item.span)),
+        };
+        let head = match decl.visibility {
+            Visibility::Public => "func pub",
+            Visibility::Private => "func",
+        };
+        let mut node = Node::new(head, Some(item.span));
+        node.children.push(Node::leaf("name", &decl.name));
+        node.children.push(Node::leaf("type", &decl.return_type));
+
+        let mut block = Node::new("block", Some(decl.body.span));
+        block.children.push(Node::expr(&decl.body.value.expr));
+        node.children.push(block);
+        node
+    }
+
+    fn expr(expr: &Spanned<Expr>) -> Node {
+        match &expr.value {
+            Expr::Integer(i) => Node::new(format!("integer {}", i), Some(expr.span)),
+            Expr::Call(callee) => {
+                let mut node = Node::new("call", Some(expr.span));
+                node.children.push(Node::leaf("name", callee));
+                node
+            }
+            Expr::Error => Node::new("error", Some(expr.span)),
+        }
+    }
+
+    fn write(&self, out: &mut String, depth: usize, spans: bool) {
+        out.push('(');
+        out.push_str(&self.head);
+        if let Some(span) = self.span.filter(|_| spans) {
+            out.push(' ');
+            out.push_str(&span.to_string());
+        }
+        for child in &self.children {
+            out.push('\n');
+            out.push_str(&"  ".repeat(depth + 1));
+            child.write(out, depth + 1, spans);
+        }
+        out.push(')');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{formatter, parser, text::Document};
+
+    fn parse(text: &str) -> Module {
+        parser::parse(&Document::new(text)).unwrap()
+    }
+
+    #[test]
+    pub fn dump_module() {
+        let module = parse("module m;\nimport a.b;\npub func f(): int { g() }");
+        assert_eq!(
+            dump(&module, true),
+            "(module 1:8-3:26
+  (name m 1:8-1:9)
+  (import a.b 2:1-2:12)
+  (func pub 3:1-3:26
+    (name f 3:10-3:11)
+    (type int 3:15-3:18)
+    (block 3:19-3:26
+      (call 3:21-3:24
+        (name g 3:21-3:22)))))
+"
+        );
+    }
+
+    #[test]
+    pub fn dump_without_spans() {
+        let module = parse("func main(): int {\n    42\n}");
+        assert_eq!(
+            dump(&module, false),
+            "(module\n  (func\n    (name main)\n    (type int)\n    (block\n      (integer 42))))\n"
+        );
+        assert_eq!(dump(&Module::default(), true), "(module)\n");
+    }
+
+    #[test]
+    pub fn dump_errors() {
+        let (module, _) = parser::parse_recovering(&Document::new("7 func f(): int { }"));
+        assert_eq!(
+            dump(&module, false),
+            "(module\n  (error)\n  (func\n    (name f)\n    (type int)\n    (block\n      (error))))\n"
+        );
+    }
+
+    #[test]
+    pub fn examples_round_trip() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        let mut count = 0;
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|e| e != "spz") {
+                continue;
+            }
+            let module = parse(&std::fs::read_to_string(&path).unwrap());
+            let printed = formatter::print(&module);
+            let reparsed = parse(&printed);
+
+            assert_eq!(
+                dump(&reparsed, false),
+                dump(&module, false),
+                "{} changed printing it as:\n{}",
+                path.display(),
+                printed
+            );
+            count += 1;
+        }
+        assert!(count > 0);
+    }
+}
@@ -0,0 +1,218 @@
+//! Interned strings for identifiers and keywords
+//!
+//! A `Symbol` is a handle to a string in an interner, so comparing, hashing and copying names
+//! costs no more than doing it to an integer.
+//!
+//! Interned strings last as long as their interner. Names lexed outside a `Session` go in a global
+//! interner that lives until the process exits, which suits compiling a program: only the names
+//! in it are ever interned. The language server and the REPL lex text as it's typed, so each
+//! interns into a session of its own instead and frees its names when the session is dropped. The
+//! language server starts a new session for each message, since it reparses documents for every
+//! request, and the REPL keeps one for as long as it runs, since its definitions do.
+//!
+//! A symbol only means something in the session it was interned in, apart from the keywords and
+//! `main`, which every interner starts with.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    convert::TryFrom,
+    rc::Rc,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+/// A handle to an interned string. Two symbols are equal exactly when their strings are.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+/// The strings interned before anything else, in the order of their symbols.
+const PREINTERNED: &[&str] = &["func", "int", "module", "import", "pub", "main"];
+
+impl Symbol {
+    pub const FUNC: Symbol = Symbol(0);
+    pub const INT: Symbol = Symbol(1);
+    pub const MODULE: Symbol = Symbol(2);
+    pub const IMPORT: Symbol = Symbol(3);
+    pub const PUB: Symbol = Symbol(4);
+    /// The name of the function programs start at.
+    pub const MAIN: Symbol = Symbol(5);
+
+    /// Gets the symbol for `s`, interning it if this is the first time it's been seen.
+    pub fn intern(s: &str) -> Symbol {
+        with_interner(|interner| match interner.symbols.get(s) {
+            Some(&symbol) => symbol,
+            None => interner.insert(s),
+        })
+    }
+
+    /// Gets the interned string, which stays alive while it's held even if its session ends.
+    fn text(self) -> Arc<str> {
+        with_interner(|interner| interner.strings[self.0 as usize].clone())
+    }
+
+    /// Checks whether the symbol is one of the language's keywords.
+    pub fn is_keyword(self) -> bool {
+        self <= Symbol::PUB
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(s: &str) -> Symbol {
+        Symbol::intern(s)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        *self.text() == *other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        *self.text() == **other
+    }
+}
+
+impl std::fmt::Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.pad(&self.text())
+    }
+}
+
+impl std::fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", &*self.text())
+    }
+}
+
+/// An interner of its own for a run of a tool like the language server, whose names are freed
+/// when the last handle to it is dropped.
+#[derive(Clone)]
+pub struct Session {
+    interner: Rc<RefCell<Interner>>,
+}
+
+impl Default for Session {
+    fn default() -> Session {
+        Session::new()
+    }
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session {
+            interner: Rc::new(RefCell::new(Interner::new())),
+        }
+    }
+
+    /// Runs `f` with symbols interned in and looked up from this session, going back to the
+    /// interner in use before once it returns.
+    pub fn enter<T>(&self, f: impl FnOnce() -> T) -> T {
+        /// Puts the previous interner back, even if `f` panics.
+        struct Exit(Option<Rc<RefCell<Interner>>>);
+
+        impl Drop for Exit {
+            fn drop(&mut self) {
+                CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+            }
+        }
+
+        let previous = CURRENT.with(|current| current.replace(Some(self.interner.clone())));
+        let _exit = Exit(previous);
+        f()
+    }
+}
+
+thread_local! {
+    /// The interner of the session entered on this thread, if there is one.
+    static CURRENT: RefCell<Option<Rc<RefCell<Interner>>>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` with the current session's interner, or the global one outside a session.
+fn with_interner<T>(f: impl FnOnce(&mut Interner) -> T) -> T {
+    let session = CURRENT.with(|current| current.borrow().clone());
+    match session {
+        Some(interner) => f(&mut interner.borrow_mut()),
+        None => f(&mut global().lock().unwrap()),
+    }
+}
+
+struct Interner {
+    symbols: HashMap<Arc<str>, Symbol>,
+    strings: Vec<Arc<str>>,
+}
+
+impl Interner {
+    fn new() -> Interner {
+        let mut interner = Interner {
+            symbols: HashMap::new(),
+            strings: Vec::new(),
+        };
+        for s in PREINTERNED {
+            interner.insert(s);
+        }
+        interner
+    }
+
+    fn insert(&mut self, s: &str) -> Symbol {
+        let index = u32::try_from(self.strings.len()).expect("too many symbols");
+        let symbol = Symbol(index);
+        let s: Arc<str> = s.into();
+        self.symbols.insert(s.clone(), symbol);
+        self.strings.push(s);
+        symbol
+    }
+}
+
+fn global() -> &'static Mutex<Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(|| Mutex::new(Interner::new()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn intern() {
+        let a = Symbol::intern("interned");
+        assert_eq!(Symbol::intern("interned"), a);
+        assert_ne!(Symbol::intern("interned2"), a);
+        assert_eq!(a.to_string(), "interned");
+        assert_eq!(a, "interned");
+        assert_eq!(format!("{:>10}|{:?}", a, a), "  interned|\"interned\"");
+    }
+
+    #[test]
+    pub fn preinterned() {
+        for (i, s) in PREINTERNED.iter().enumerate() {
+            assert_eq!(Symbol::intern(s), Symbol(i as u32));
+        }
+        assert_eq!(Symbol::intern("func"), Symbol::FUNC);
+        assert_eq!(Symbol::intern("pub"), Symbol::PUB);
+        assert!(Symbol::IMPORT.is_keyword());
+        assert!(!Symbol::MAIN.is_keyword());
+        assert!(!Symbol::intern("function").is_keyword());
+    }
+
+    #[test]
+    pub fn session() {
+        let outside = Symbol::intern("outside the session");
+        let session = Session::new();
+        let inside = session.enter(|| {
+            assert_eq!(Symbol::intern("main"), Symbol::MAIN);
+            let inside = Symbol::intern("inside the session");
+            assert_eq!(Symbol::intern("inside the session"), inside);
+            assert_eq!(inside, "inside the session");
+            inside
+        });
+        assert_eq!(outside, "outside the session");
+
+        // The session's names are only there while it's entered, and gone once it's dropped.
+        assert_eq!(session.enter(|| inside.to_string()), "inside the session");
+        let weak = Rc::downgrade(&session.interner);
+        drop(session);
+        assert!(weak.upgrade().is_none());
+    }
+}
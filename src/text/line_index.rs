@@ -0,0 +1,497 @@
+//! Conversions between byte offsets and line/column positions
+//!
+//! A [`LineIndex`] records where each line starts, plus every character wider than one byte and
+//! every grapheme cluster longer than one character. Most source is ASCII, so those lists are
+//! usually short, and each conversion is a few binary searches over them.
+
+use std::ops::Range;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::text::{LineEndings, Location, TextEdit};
+
+/// The unit a column is counted in.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ColumnUnit {
+    Byte,
+    Char,
+    /// UTF-16 code units, as used by editors and the Language Server Protocol.
+    Utf16,
+    /// Extended grapheme clusters, roughly what a reader would call a character.
+    Grapheme,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+    /// Where the content of each line ends, before its terminator.
+    line_ends: Vec<usize>,
+    /// Offsets of the characters encoded in more than one byte.
+    wide_chars: Vec<usize>,
+    /// Running totals of the bytes in `wide_chars` beyond one per character. Entry `i` covers the
+    /// characters before `wide_chars[i]`, and there is one extra entry for the grand total.
+    extra_bytes: Vec<usize>,
+    /// Running totals of the bytes in `wide_chars` beyond one per UTF-16 code unit.
+    extra_utf16: Vec<usize>,
+    /// Grapheme clusters made of more than one character.
+    clusters: Vec<Cluster>,
+    /// Running totals of the characters in `clusters` beyond one per cluster.
+    extra_chars: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cluster {
+    range: Range<usize>,
+    /// The character column the cluster starts at.
+    column: usize,
+}
+
+impl LineIndex {
+    pub fn new(text: &str, line_endings: LineEndings) -> LineIndex {
+        let mut index = LineIndex::empty();
+        index.scan_lines(text, 0..text.len(), line_endings);
+        index.line_ends.push(text.len());
+        index.scan_clusters(text, 0..text.len());
+        index
+    }
+
+    fn empty() -> LineIndex {
+        LineIndex {
+            line_starts: vec![0],
+            line_ends: Vec::new(),
+            wide_chars: Vec::new(),
+            extra_bytes: vec![0],
+            extra_utf16: vec![0],
+            clusters: Vec::new(),
+            extra_chars: vec![0],
+        }
+    }
+
+    /// Builds the index for `text`, which is the text this index was built for with `edit`
+    /// applied.
+    ///
+    /// Only the lines the edit touches are scanned again. Everything after them is the same text
+    /// as before, so its entries are copied over, moved by however much the edit changed the
+    /// length of the text.
+    pub fn edited(&self, edit: &TextEdit, text: &str, line_endings: LineEndings) -> LineIndex {
+        // Start a line early, since the edit may join its first line to a '\r' ending the one
+        // before. Grapheme clusters never continue past a line terminator, so scanning whole
+        // lines splits them just as scanning the whole text does.
+        let start = self.line_starts[self.line(edit.range.start.saturating_sub(1))];
+        // The first line starting after the edit still starts where it did, moved by the edit.
+        let after = self.line_starts.partition_point(|&o| o <= edit.range.end);
+        let moved = |offset: usize| offset + edit.replacement.len() - edit.range.len();
+        let end = self
+            .line_starts
+            .get(after)
+            .map_or(text.len(), |&o| moved(o));
+
+        let mut index = LineIndex::empty();
+        let line = self.line(start);
+        index.line_starts = self.line_starts[..=line].to_vec();
+        index.line_ends = self.line_ends[..line].to_vec();
+        let wide = self.wide_chars.partition_point(|&o| o < start);
+        index.wide_chars = self.wide_chars[..wide].to_vec();
+        index.extra_bytes = self.extra_bytes[..=wide].to_vec();
+        index.extra_utf16 = self.extra_utf16[..=wide].to_vec();
+
+        index.scan_lines(text, start..end, line_endings);
+        if after < self.line_starts.len() {
+            index
+                .line_starts
+                .extend(self.line_starts[after + 1..].iter().map(|&o| moved(o)));
+            index
+                .line_ends
+                .extend(self.line_ends[after..].iter().map(|&o| moved(o)));
+            let first = self
+                .wide_chars
+                .partition_point(|&o| o < self.line_starts[after]);
+            for i in first..self.wide_chars.len() {
+                let last = index.wide_chars.len();
+                index.wide_chars.push(moved(self.wide_chars[i]));
+                index
+                    .extra_bytes
+                    .push(index.extra_bytes[last] + self.extra_bytes[i + 1] - self.extra_bytes[i]);
+                index
+                    .extra_utf16
+                    .push(index.extra_utf16[last] + self.extra_utf16[i + 1] - self.extra_utf16[i]);
+            }
+        } else {
+            index.line_ends.push(text.len());
+        }
+
+        let clusters = self.clusters.partition_point(|c| c.range.start < start);
+        index.clusters = self.clusters[..clusters].to_vec();
+        index.extra_chars = self.extra_chars[..=clusters].to_vec();
+        index.scan_clusters(text, start..end);
+        if let Some(&old_end) = self.line_starts.get(after) {
+            let first = self.clusters.partition_point(|c| c.range.start < old_end);
+            for i in first..self.clusters.len() {
+                let cluster = &self.clusters[i];
+                let total = index.extra_chars[index.clusters.len()];
+                index.clusters.push(Cluster {
+                    range: moved(cluster.range.start)..moved(cluster.range.end),
+                    column: cluster.column,
+                });
+                index
+                    .extra_chars
+                    .push(total + self.extra_chars[i + 1] - self.extra_chars[i]);
+            }
+        }
+
+        index
+    }
+
+    /// Records the lines ending and characters wider than a byte in `range`, which starts at the
+    /// start of a line.
+    fn scan_lines(&mut self, text: &str, range: Range<usize>, line_endings: LineEndings) {
+        let bytes = text.as_bytes();
+        for (offset, c) in text[range.clone()].char_indices() {
+            let offset = range.start + offset;
+            let next = offset + c.len_utf8();
+            if line_endings.ends_line(c, bytes.get(next) == Some(&b'\n')) {
+                let crlf = c == '\n' && offset > 0 && bytes[offset - 1] == b'\r';
+                if crlf && line_endings != LineEndings::Lf {
+                    self.line_ends.push(offset - 1);
+                } else {
+                    self.line_ends.push(offset);
+                }
+                self.line_starts.push(next);
+            }
+            if c.len_utf8() > 1 {
+                let bytes = self.extra_bytes[self.wide_chars.len()];
+                let utf16 = self.extra_utf16[self.wide_chars.len()];
+                self.wide_chars.push(offset);
+                self.extra_bytes.push(bytes + c.len_utf8() - 1);
+                self.extra_utf16.push(utf16 + c.len_utf8() - c.len_utf16());
+            }
+        }
+    }
+
+    /// Records the grapheme clusters in `range` made of more than one character, once its lines
+    /// have been scanned.
+    fn scan_clusters(&mut self, text: &str, range: Range<usize>) {
+        for (offset, cluster) in text[range.clone()].grapheme_indices(true) {
+            let offset = range.start + offset;
+            let chars = cluster.chars().count();
+            if chars > 1 {
+                let column = self.column(offset, ColumnUnit::Char);
+                let total = self.extra_chars[self.clusters.len()];
+                self.clusters.push(Cluster {
+                    range: offset..offset + cluster.len(),
+                    column,
+                });
+                self.extra_chars.push(total + chars - 1);
+            }
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Gets the zero-based line containing `offset`.
+    pub fn line(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= offset) - 1
+    }
+
+    /// Gets the byte range of `line`, excluding its line terminator.
+    pub fn line_range(&self, line: usize) -> Option<Range<usize>> {
+        Some(*self.line_starts.get(line)?..*self.line_ends.get(line)?)
+    }
+
+    /// Gets the full location of `offset`, with its column counted in characters.
+    pub fn location(&self, offset: usize) -> Location {
+        Location::new(
+            offset,
+            self.line(offset),
+            self.column(offset, ColumnUnit::Char),
+        )
+    }
+
+    /// Gets the column of `offset` within its line, counted in `unit`.
+    ///
+    /// `offset` must lie on a character boundary. When counting graphemes, an offset inside a
+    /// cluster gets the column of the cluster.
+    pub fn column(&self, offset: usize, unit: ColumnUnit) -> usize {
+        let start = self.line_starts[self.line(offset)];
+        let wide = self.wide_chars.partition_point(|&o| o < start)
+            ..self.wide_chars.partition_point(|&o| o < offset);
+
+        match unit {
+            ColumnUnit::Byte => offset - start,
+            ColumnUnit::Char => {
+                offset - start - (self.extra_bytes[wide.end] - self.extra_bytes[wide.start])
+            }
+            ColumnUnit::Utf16 => {
+                offset - start - (self.extra_utf16[wide.end] - self.extra_utf16[wide.start])
+            }
+            ColumnUnit::Grapheme => {
+                let after = self.clusters.partition_point(|c| c.range.end <= offset);
+                let offset = match self.clusters.get(after) {
+                    Some(c) if c.range.start < offset => c.range.start,
+                    _ => offset,
+                };
+                let clusters = self.clusters.partition_point(|c| c.range.start < start)
+                    ..self.clusters.partition_point(|c| c.range.start < offset);
+                self.column(offset, ColumnUnit::Char)
+                    - (self.extra_chars[clusters.end] - self.extra_chars[clusters.start])
+            }
+        }
+    }
+
+    /// Gets the byte offset of `column` on `line`, with the column counted in `unit`.
+    ///
+    /// Columns past the end of the line are clamped to its end, and columns inside a character
+    /// or grapheme cluster are moved back to its start. Returns `None` if there is no such line.
+    pub fn offset(&self, line: usize, column: usize, unit: ColumnUnit) -> Option<usize> {
+        let range = self.line_range(line)?;
+        let start = range.start;
+        let first = self.wide_chars.partition_point(|&o| o < start);
+        let count = self.wide_chars.partition_point(|&o| o < range.end) - first;
+
+        let offset = match unit {
+            ColumnUnit::Byte => {
+                let offset = (start + column).min(range.end);
+                let before = self.wide_chars.partition_point(|&o| o < offset);
+                match before.checked_sub(1) {
+                    Some(i) if self.wide_chars[i] + self.len_utf8(i) > offset => self.wide_chars[i],
+                    _ => offset,
+                }
+            }
+            ColumnUnit::Char => {
+                let extra = &self.extra_bytes[first..];
+                let column_of =
+                    |i: usize| self.wide_chars[first + i] - start - (extra[i] - extra[0]);
+                let before = partition_point(count, |i| column_of(i) < column);
+                start + column + (extra[before] - extra[0])
+            }
+            ColumnUnit::Utf16 => {
+                let extra = &self.extra_utf16[first..];
+                let column_of =
+                    |i: usize| self.wide_chars[first + i] - start - (extra[i] - extra[0]);
+                let before = partition_point(count, |i| column_of(i) < column);
+
+                // A column pointing at the second half of a surrogate pair means the first half.
+                if before > 0 {
+                    let last = before - 1;
+                    let len_utf16 = self.len_utf8(first + last) - (extra[last + 1] - extra[last]);
+                    if column_of(last) + len_utf16 > column {
+                        return Some(self.wide_chars[first + last]);
+                    }
+                }
+                start + column + (extra[before] - extra[0])
+            }
+            ColumnUnit::Grapheme => {
+                let first = self.clusters.partition_point(|c| c.range.start < start);
+                let count = self.clusters.partition_point(|c| c.range.start < range.end) - first;
+                let extra = &self.extra_chars[first..];
+                let column_of = |i: usize| self.clusters[first + i].column - (extra[i] - extra[0]);
+                let before = partition_point(count, |i| column_of(i) < column);
+                let chars = column + (extra[before] - extra[0]);
+                return self.offset(line, chars, ColumnUnit::Char);
+            }
+        };
+        Some(offset.min(range.end))
+    }
+
+    fn len_utf8(&self, wide_char: usize) -> usize {
+        self.extra_bytes[wide_char + 1] - self.extra_bytes[wide_char] + 1
+    }
+}
+
+/// Finds the first index in `0..len` where `pred` is false, given it is true for a prefix.
+fn partition_point<F: Fn(usize) -> bool>(len: usize, pred: F) -> usize {
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if pred(mid) {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNITS: [ColumnUnit; 4] = [
+        ColumnUnit::Byte,
+        ColumnUnit::Char,
+        ColumnUnit::Utf16,
+        ColumnUnit::Grapheme,
+    ];
+
+    /// Computes a column the slow way, straight from the text.
+    fn reference_column(text: &str, offset: usize, unit: ColumnUnit) -> usize {
+        let start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let prefix = &text[start..offset];
+        match unit {
+            ColumnUnit::Byte => prefix.len(),
+            ColumnUnit::Char => prefix.chars().count(),
+            ColumnUnit::Utf16 => prefix.encode_utf16().count(),
+            ColumnUnit::Grapheme => text[start..]
+                .grapheme_indices(true)
+                .take_while(|(i, g)| start + i + g.len() <= offset)
+                .count(),
+        }
+    }
+
+    /// Finds the start of the grapheme cluster containing `offset` the slow way, by walking the
+    /// clusters of its line.
+    fn reference_cluster_start(text: &str, offset: usize) -> usize {
+        let start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+        text[start..]
+            .grapheme_indices(true)
+            .map(|(i, _)| start + i)
+            .chain(std::iter::once(text.len()))
+            .take_while(|&boundary| boundary <= offset)
+            .last()
+            .unwrap_or(start)
+    }
+
+    #[test]
+    pub fn lines() {
+        let index = LineIndex::new("ab\n\ncd\n", LineEndings::default());
+        assert_eq!(index.line_count(), 4);
+        assert_eq!(index.line(0), 0);
+        assert_eq!(index.line(2), 0);
+        assert_eq!(index.line(3), 1);
+        assert_eq!(index.line(4), 2);
+        assert_eq!(index.line(7), 3);
+        assert_eq!(index.line_range(0), Some(0..2));
+        assert_eq!(index.line_range(1), Some(3..3));
+        assert_eq!(index.line_range(2), Some(4..6));
+        assert_eq!(index.line_range(3), Some(7..7));
+        assert_eq!(index.line_range(4), None);
+    }
+
+    #[test]
+    pub fn line_endings() {
+        let text = "a\r\nb\rc\u{2028}d";
+        let index = LineIndex::new(text, LineEndings::Unicode);
+        assert_eq!(index.line_count(), 4);
+        assert_eq!(index.line_range(0), Some(0..1));
+        assert_eq!(index.line_range(1), Some(3..4));
+        assert_eq!(index.line_range(2), Some(5..6));
+        assert_eq!(index.line_range(3), Some(9..10));
+        // The '\n' of a '\r\n' is still on the line it ends.
+        assert_eq!(index.line(2), 0);
+
+        let index = LineIndex::new(text, LineEndings::Ascii);
+        assert_eq!(index.line_count(), 3);
+        assert_eq!(index.line_range(2), Some(5..10));
+
+        let index = LineIndex::new(text, LineEndings::Lf);
+        assert_eq!(index.line_count(), 2);
+        assert_eq!(index.line_range(0), Some(0..2));
+        assert_eq!(index.column(10, ColumnUnit::Char), 5);
+    }
+
+    #[test]
+    pub fn columns() {
+        // 'é' is two bytes, '✨' is three, '𝒜' is four bytes and two UTF-16 code units, and
+        // 'e\u{301}' is two characters but one grapheme.
+        let text = "aé✨\n𝒜e\u{301}b";
+        let index = LineIndex::new(text, LineEndings::default());
+
+        let b = text.find('b').unwrap();
+        assert_eq!(index.column(b, ColumnUnit::Byte), 7);
+        assert_eq!(index.column(b, ColumnUnit::Char), 3);
+        assert_eq!(index.column(b, ColumnUnit::Utf16), 4);
+        assert_eq!(index.column(b, ColumnUnit::Grapheme), 2);
+        assert_eq!(index.location(b), Location::new(b, 1, 3));
+
+        let newline = text.find('\n').unwrap();
+        assert_eq!(index.location(newline), Location::new(6, 0, 3));
+    }
+
+    #[test]
+    pub fn columns_match_reference() {
+        let texts = [
+            "",
+            "plain ascii\nlines\n",
+            "aé✨\n𝒜e\u{301}b",
+            "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467} family\r\nnext",
+            "京¾৬\n\n✨✨✨",
+        ];
+        for text in texts.iter() {
+            let index = LineIndex::new(text, LineEndings::default());
+            for (offset, _) in text
+                .char_indices()
+                .chain(std::iter::once((text.len(), ' ')))
+            {
+                for unit in UNITS.iter() {
+                    assert_eq!(
+                        index.column(offset, *unit),
+                        reference_column(text, offset, *unit),
+                        "{:?} at {} in {:?}",
+                        unit,
+                        offset,
+                        text
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn offsets_round_trip() {
+        let texts = [
+            "plain ascii\nlines\n",
+            "aé✨\n𝒜e\u{301}b",
+            "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467} family\r\nnext",
+        ];
+        for text in texts.iter() {
+            let index = LineIndex::new(text, LineEndings::default());
+            for (offset, _) in text
+                .char_indices()
+                .chain(std::iter::once((text.len(), ' ')))
+            {
+                let line = index.line(offset);
+                // Offsets within a line terminator are clamped to the end of the line.
+                if offset > index.line_range(line).unwrap().end {
+                    continue;
+                }
+                for unit in UNITS.iter() {
+                    let column = index.column(offset, *unit);
+                    let expected = match unit {
+                        // Offsets inside a cluster come back as the cluster's start.
+                        ColumnUnit::Grapheme => reference_cluster_start(text, offset),
+                        _ => offset,
+                    };
+                    assert_eq!(
+                        index.offset(line, column, *unit),
+                        Some(expected),
+                        "{:?} at {} in {:?}",
+                        unit,
+                        offset,
+                        text
+                    );
+                    assert_eq!(index.column(expected, *unit), column);
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn offset_clamping() {
+        let text = "a𝒜b\nc";
+        let index = LineIndex::new(text, LineEndings::default());
+
+        // Past the end of the line.
+        assert_eq!(index.offset(0, 99, ColumnUnit::Char), Some(6));
+        assert_eq!(index.offset(0, 99, ColumnUnit::Utf16), Some(6));
+        assert_eq!(index.offset(1, 99, ColumnUnit::Grapheme), Some(8));
+        // Inside a character.
+        assert_eq!(index.offset(0, 3, ColumnUnit::Byte), Some(1));
+        // Between the two halves of a surrogate pair.
+        assert_eq!(index.offset(0, 2, ColumnUnit::Utf16), Some(1));
+        assert_eq!(index.offset(0, 3, ColumnUnit::Utf16), Some(5));
+        // No such line.
+        assert_eq!(index.offset(2, 0, ColumnUnit::Char), None);
+    }
+}
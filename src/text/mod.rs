@@ -0,0 +1,1249 @@
+//! Text management APIs
+
+mod line_index;
+mod sources;
+
+use std::sync::Arc;
+
+pub use line_index::{ColumnUnit, LineIndex};
+pub use sources::{FileId, SourceDatabase};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TextError {
+    InvalidUtf8(InvalidUtf8),
+    OutOfBounds,
+}
+
+impl std::fmt::Display for TextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TextError::InvalidUtf8(invalid) => write!(f, "{}", invalid),
+            TextError::OutOfBounds => write!(f, "read past the end of the document"),
+        }
+    }
+}
+
+/// A sequence of bytes that isn't valid UTF-8, which stands in the document's text as a single
+/// U+FFFD REPLACEMENT CHARACTER.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidUtf8 {
+    pub bytes: Vec<u8>,
+    /// Where the bytes were in what the document was loaded from. Offsets in the text differ once
+    /// an earlier sequence has been replaced.
+    pub source_offset: usize,
+    /// Where the replacement character is in the text.
+    pub offset: usize,
+}
+
+impl std::fmt::Display for InvalidUtf8 {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.bytes[..] {
+            [b] => write!(f, "invalid UTF-8 byte 0x{:02X}", b)?,
+            bytes => {
+                write!(f, "invalid UTF-8 sequence")?;
+                for b in bytes {
+                    write!(f, " 0x{:02X}", b)?;
+                }
+            }
+        }
+        write!(f, " at byte {}", self.source_offset)
+    }
+}
+
+/// The byte order mark some editors start UTF-8 files with.
+pub const BOM: char = '\u{FEFF}';
+const BOM_BYTES: &[u8] = b"\xEF\xBB\xBF";
+
+/// Which characters end a line.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum LineEndings {
+    /// Only `\n`.
+    Lf,
+    /// `\n`, `\r\n` and a lone `\r`, as used by the Language Server Protocol.
+    Ascii,
+    /// Everything in `Ascii`, plus NEL (U+0085), LINE SEPARATOR (U+2028) and PARAGRAPH SEPARATOR
+    /// (U+2029).
+    #[default]
+    Unicode,
+}
+
+impl LineEndings {
+    /// Determines if `c` ends a line. A `\r` followed by `\n` doesn't, the `\n` does instead.
+    pub fn ends_line(self, c: char, followed_by_lf: bool) -> bool {
+        match c {
+            '\n' => true,
+            '\r' => self != LineEndings::Lf && !followed_by_lf,
+            '\u{85}' | '\u{2028}' | '\u{2029}' => self == LineEndings::Unicode,
+            _ => false,
+        }
+    }
+}
+
+pub struct Document {
+    pub text: String,
+    file: FileId,
+    line_endings: LineEndings,
+    line_index: std::sync::OnceLock<Arc<LineIndex>>,
+    /// The sequences replaced when the document was loaded from bytes, in order.
+    invalid_utf8: Vec<InvalidUtf8>,
+    /// Whether the text started with a byte order mark, which isn't kept in `text`.
+    bom: bool,
+    normalize_identifiers: bool,
+}
+
+impl Document {
+    pub fn new<S: Into<String>>(text: S) -> Document {
+        Document::with_line_endings(text, LineEndings::default())
+    }
+
+    /// Creates a document from `text`, leaving out the byte order mark it starts with, if any.
+    pub fn with_line_endings<S: Into<String>>(text: S, line_endings: LineEndings) -> Document {
+        let mut text = text.into();
+        let bom = text.starts_with(BOM);
+        if bom {
+            text.drain(..BOM.len_utf8());
+        }
+        Document::from_parts(text, line_endings, bom, Vec::new())
+    }
+
+    /// Loads a document from bytes that should be UTF-8. Each invalid sequence is replaced with
+    /// U+FFFD REPLACEMENT CHARACTER and recorded, so it can be reported when the text is lexed.
+    pub fn from_bytes(bytes: &[u8]) -> Document {
+        Document::from_bytes_with_line_endings(bytes, LineEndings::default())
+    }
+
+    pub fn from_bytes_with_line_endings(bytes: &[u8], line_endings: LineEndings) -> Document {
+        let mut text = String::with_capacity(bytes.len());
+        let mut invalid_utf8 = Vec::new();
+        let bom = bytes.starts_with(BOM_BYTES);
+        let mut rest = if bom {
+            &bytes[BOM_BYTES.len()..]
+        } else {
+            bytes
+        };
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    break;
+                }
+                Err(e) => {
+                    let (valid, after) = rest.split_at(e.valid_up_to());
+                    // Valid up to the error, so this can't fail.
+                    text.push_str(std::str::from_utf8(valid).unwrap_or_default());
+
+                    // No error length means the input ended partway through a sequence.
+                    let len = e.error_len().unwrap_or(after.len());
+                    invalid_utf8.push(InvalidUtf8 {
+                        bytes: after[..len].to_vec(),
+                        source_offset: bytes.len() - after.len(),
+                        offset: text.len(),
+                    });
+                    text.push(char::REPLACEMENT_CHARACTER);
+                    rest = &after[len..];
+                }
+            }
+        }
+
+        Document::from_parts(text, line_endings, bom, invalid_utf8)
+    }
+
+    fn from_parts(
+        text: String,
+        line_endings: LineEndings,
+        bom: bool,
+        invalid_utf8: Vec<InvalidUtf8>,
+    ) -> Document {
+        Document {
+            text,
+            file: FileId::DETACHED,
+            line_endings,
+            line_index: std::sync::OnceLock::new(),
+            invalid_utf8,
+            bom,
+            normalize_identifiers: true,
+        }
+    }
+
+    /// Creates a document for `text`, a piece of this one, that lexes the way this one does: with
+    /// the same line endings and identifier normalization.
+    pub fn fragment<S: Into<String>>(&self, text: S) -> Document {
+        let mut fragment = Document::from_parts(text.into(), self.line_endings, false, Vec::new());
+        fragment.normalize_identifiers = self.normalize_identifiers;
+        fragment
+    }
+
+    /// Gets the file the document was added to, or `FileId::DETACHED` if it isn't part of a
+    /// `SourceDatabase`.
+    pub fn file(&self) -> FileId {
+        self.file
+    }
+
+    pub fn line_endings(&self) -> LineEndings {
+        self.line_endings
+    }
+
+    /// Determines if the document started with a byte order mark, so it can be put back when the
+    /// text is written out.
+    pub fn has_bom(&self) -> bool {
+        self.bom
+    }
+
+    /// Determines if identifiers are converted to Unicode Normalization Form C when lexed, so the
+    /// same name always gives the same symbol however its characters were composed. They are
+    /// unless turned off with `set_normalize_identifiers`.
+    pub fn normalizes_identifiers(&self) -> bool {
+        self.normalize_identifiers
+    }
+
+    pub fn set_normalize_identifiers(&mut self, normalize: bool) {
+        self.normalize_identifiers = normalize;
+    }
+
+    /// Gets the line index for the document, building it the first time it's needed.
+    pub fn line_index(&self) -> &LineIndex {
+        self.shared_line_index()
+    }
+
+    /// Gets the line index for the document as a handle that can outlive the borrow, for trees
+    /// built over the document.
+    pub fn shared_line_index(&self) -> &Arc<LineIndex> {
+        self.line_index
+            .get_or_init(|| Arc::new(LineIndex::new(&self.text, self.line_endings)))
+    }
+
+    pub fn text_at(&self, sp: Span) -> &str {
+        &self.text[sp.start.offset..sp.end.offset]
+    }
+
+    /// Gets the invalid UTF-8 replaced when the document was loaded.
+    pub fn invalid_utf8(&self) -> &[InvalidUtf8] {
+        &self.invalid_utf8
+    }
+
+    /// Gets the invalid UTF-8 sequence replaced at `offset` in the text, if there is one.
+    pub fn invalid_utf8_at(&self, offset: usize) -> Option<&InvalidUtf8> {
+        self.invalid_utf8
+            .binary_search_by_key(&offset, |invalid| invalid.offset)
+            .ok()
+            .map(|i| &self.invalid_utf8[i])
+    }
+
+    /// Applies `edit` to the text, keeping the document's file and line endings. A line index
+    /// that was already built is updated for the lines the edit touches.
+    pub fn apply(&mut self, edit: &TextEdit) {
+        self.text
+            .replace_range(edit.range.clone(), &edit.replacement);
+        if let Some(index) = self.line_index.take() {
+            let index = index.edited(edit, &self.text, self.line_endings);
+            self.line_index = std::sync::OnceLock::from(Arc::new(index));
+        }
+
+        // Replacement characters the edit removed are gone, and those after it have moved.
+        let range = &edit.range;
+        self.invalid_utf8
+            .retain(|invalid| invalid.offset < range.start || invalid.offset >= range.end);
+        for invalid in &mut self.invalid_utf8 {
+            if invalid.offset >= range.end {
+                invalid.offset = invalid.offset - range.len() + edit.replacement.len();
+            }
+        }
+    }
+}
+
+/// Replaces a range of bytes in a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: std::ops::Range<usize>,
+    pub replacement: String,
+}
+
+impl TextEdit {
+    pub fn new<S: Into<String>>(range: std::ops::Range<usize>, replacement: S) -> TextEdit {
+        TextEdit {
+            range,
+            replacement: replacement.into(),
+        }
+    }
+
+    /// Applies the edit to `text`, where the range starts at `offset` rather than at zero.
+    pub fn apply_at(&self, text: &str, offset: usize) -> String {
+        let start = self.range.start - offset;
+        let end = self.range.end - offset;
+        [&text[..start], &self.replacement, &text[end..]].concat()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Location {
+    pub offset: usize,
+    pub column: usize,
+    pub line: usize,
+}
+
+impl Location {
+    pub const ZERO: Location = Location {
+        offset: 0,
+        column: 0,
+        line: 0,
+    };
+
+    pub fn new(offset: usize, line: usize, column: usize) -> Location {
+        Location {
+            offset,
+            column,
+            line,
+        }
+    }
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // Lines and columns are zero-based internally, but one-based for humans.
+        write!(f, "{}:{}", self.line + 1, self.column + 1)
+    }
+}
+
+impl From<Location> for (usize, usize, usize) {
+    fn from(l: Location) -> (usize, usize, usize) {
+        (l.offset, l.line, l.column)
+    }
+}
+
+impl From<(usize, usize, usize)> for Location {
+    fn from((o, l, c): (usize, usize, usize)) -> Location {
+        Location::new(o, l, c)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct NonContiguousSpansError;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Span {
+    pub file: FileId,
+    pub start: Location,
+    pub end: Location,
+}
+
+impl Span {
+    pub const ZERO: Span = Span {
+        file: FileId::DETACHED,
+        start: Location::ZERO,
+        end: Location::ZERO,
+    };
+
+    /// Creates a span in a detached document.
+    pub fn new(start: Location, end: Location) -> Span {
+        Span::in_file(FileId::DETACHED, start, end)
+    }
+
+    pub fn in_file(file: FileId, start: Location, end: Location) -> Span {
+        Span { file, start, end }
+    }
+
+    /// Returns an empty span at the end of this one.
+    pub fn at_end(&self) -> Span {
+        Span::in_file(self.file, self.end, self.end)
+    }
+
+    /// Returns a span covering both this span and `other`, including anything between them.
+    pub fn merge(&self, other: Span) -> Span {
+        debug_assert_eq!(self.file, other.file, "merging spans from different files");
+        let start = if other.start.offset < self.start.offset {
+            other.start
+        } else {
+            self.start
+        };
+        let end = if other.end.offset > self.end.offset {
+            other.end
+        } else {
+            self.end
+        };
+        Span::in_file(self.file, start, end)
+    }
+
+    pub fn append(&self, next: Span) -> Result<Span, NonContiguousSpansError> {
+        if self.file != next.file || self.end != next.start {
+            Err(NonContiguousSpansError)
+        } else {
+            Ok(Span::in_file(self.file, self.start, next.end))
+        }
+    }
+
+    fn expand(&self, c: char, ends_line: bool) -> Span {
+        let (line, column) = if ends_line {
+            (self.end.line + 1, 0)
+        } else {
+            (self.end.line, self.end.column + 1)
+        };
+
+        Span {
+            file: self.file,
+            start: self.start,
+            end: (self.end.offset + c.len_utf8(), line, column).into(),
+        }
+    }
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // Padding applies to the span as a whole.
+        f.pad(&format!("{}-{}", self.start, self.end))
+    }
+}
+
+pub trait CharPattern {
+    fn matches(self, c: char) -> bool;
+
+    /// Gets a table of the ASCII characters the pattern matches, indexed by byte, which lets
+    /// `Window::take_while` look each byte up rather than test it.
+    fn ascii_table(&self) -> Option<&[bool; 128]> {
+        None
+    }
+}
+
+/// A set of characters with its ASCII members in a table, so `Window::take_while` can scan a run
+/// of them a byte at a time. Characters outside ASCII are tested with a function.
+#[derive(Copy, Clone)]
+pub struct CharClass {
+    ascii: [bool; 128],
+    non_ascii: fn(char) -> bool,
+}
+
+impl CharClass {
+    /// Whitespace, as `char::is_whitespace` defines it.
+    pub const WHITESPACE: CharClass = CharClass::new(b" \t\n\x0B\x0C\r", char::is_whitespace);
+
+    /// Creates the class of the ASCII characters in `ascii` and the others `non_ascii` accepts.
+    pub const fn new(ascii: &[u8], non_ascii: fn(char) -> bool) -> CharClass {
+        CharClass {
+            ascii: ascii_table(ascii, true),
+            non_ascii,
+        }
+    }
+
+    /// Creates the class of the ASCII characters not in `ascii` and the others `non_ascii`
+    /// accepts.
+    pub const fn all_but(ascii: &[u8], non_ascii: fn(char) -> bool) -> CharClass {
+        CharClass {
+            ascii: ascii_table(ascii, false),
+            non_ascii,
+        }
+    }
+}
+
+/// Builds a table where the bytes in `members` are `member` and the rest aren't.
+const fn ascii_table(members: &[u8], member: bool) -> [bool; 128] {
+    let mut table = [!member; 128];
+    let mut i = 0;
+    while i < members.len() {
+        table[members[i] as usize] = member;
+        i += 1;
+    }
+    table
+}
+
+impl CharPattern for CharClass {
+    fn matches(self, c: char) -> bool {
+        match self.ascii.get(c as usize) {
+            Some(&member) => member,
+            None => (self.non_ascii)(c),
+        }
+    }
+
+    fn ascii_table(&self) -> Option<&[bool; 128]> {
+        Some(&self.ascii)
+    }
+}
+
+impl CharPattern for std::ops::Range<char> {
+    fn matches(self, c: char) -> bool {
+        self.contains(&c)
+    }
+}
+
+impl CharPattern for std::ops::RangeInclusive<char> {
+    fn matches(self, c: char) -> bool {
+        self.contains(&c)
+    }
+}
+
+impl CharPattern for char {
+    fn matches(self, c: char) -> bool {
+        self == c
+    }
+}
+
+impl<F: Fn(char) -> bool> CharPattern for F {
+    fn matches(self, c: char) -> bool {
+        self(c)
+    }
+}
+
+/// Reads the character at `at` in `document`, failing if it stands in for invalid UTF-8.
+fn read_char(document: &Document, at: usize) -> Result<(char, usize), TextError> {
+    if let Some(invalid) = document.invalid_utf8_at(at) {
+        return Err(TextError::InvalidUtf8(invalid.clone()));
+    }
+
+    // `get` fails when `at` is past the end or inside a character, rather than panicking.
+    let c = document
+        .text
+        .get(at..)
+        .and_then(|rest| rest.chars().next())
+        .ok_or(TextError::OutOfBounds)?;
+    Ok((c, at + c.len_utf8()))
+}
+
+pub struct Window<'a> {
+    document: &'a Document,
+    span: Span,
+}
+
+impl<'a> Window<'a> {
+    pub fn new(document: &'a Document) -> Window<'a> {
+        Window {
+            document,
+            span: Span::in_file(document.file, Location::ZERO, Location::ZERO),
+        }
+    }
+
+    pub fn document(&self) -> &'a Document {
+        self.document
+    }
+
+    pub fn content(&self) -> &str {
+        self.document.text_at(self.span)
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn take_many(&mut self, count: usize) -> Result<(), TextError> {
+        for _ in 0..count {
+            self.take()?;
+        }
+        Ok(())
+    }
+
+    /// Takes the next character. Invalid UTF-8 is taken too, but as an error, so the text after
+    /// it can still be read.
+    pub fn take(&mut self) -> Result<char, TextError> {
+        if let Some(invalid) = self.document.invalid_utf8_at(self.span.end.offset) {
+            self.span = self.span.expand(char::REPLACEMENT_CHARACTER, false);
+            return Err(TextError::InvalidUtf8(invalid.clone()));
+        }
+
+        match self.next() {
+            None => Err(TextError::OutOfBounds),
+            Some(c) => {
+                self.span = self.span.expand(c, self.ends_line(c));
+                Ok(c)
+            }
+        }
+    }
+
+    /// Determines if the next character ends a line under the document's line endings. In a
+    /// `\r\n`, only the `\n` does.
+    pub fn at_line_end(&self) -> bool {
+        self.next().is_some_and(|c| self.ends_line(c))
+    }
+
+    /// Determines if `c`, as the next character, ends a line.
+    fn ends_line(&self, c: char) -> bool {
+        let next_offset = self.span.end.offset + c.len_utf8();
+        let followed_by_lf = self.document.text.as_bytes().get(next_offset) == Some(&b'\n');
+        self.document.line_endings.ends_line(c, followed_by_lf)
+    }
+
+    /// Gets the next character without taking it, or `None` at the end of the document or
+    /// invalid UTF-8.
+    pub fn next(&self) -> Option<char> {
+        if self.span.end.offset + 1 > self.document.text.len() {
+            None
+        } else {
+            // Compute character width
+            let (c, _) = match read_char(self.document, self.span.end.offset) {
+                Ok(x) => x,
+                Err(_) => return None,
+            };
+            Some(c)
+        }
+    }
+
+    pub fn peek<P: CharPattern>(&self, pattern: P) -> bool {
+        match self.next() {
+            None => false,
+            Some(c) => pattern.matches(c),
+        }
+    }
+
+    pub fn take_while<P: CharPattern + Clone>(&mut self, pattern: P) -> Result<(), TextError> {
+        loop {
+            self.take_ascii_while(pattern.clone());
+            if !self.peek(pattern.clone()) {
+                return Ok(());
+            }
+            self.take()?;
+        }
+    }
+
+    /// Takes a run of ASCII characters matching `pattern` in one go, working on the bytes rather
+    /// than decoding and spanning each character in turn. Stops at the first character that
+    /// doesn't match or isn't ASCII, which is left for `take`.
+    fn take_ascii_while<P: CharPattern + Clone>(&mut self, pattern: P) {
+        let rest = &self.document.text.as_bytes()[self.span.end.offset..];
+        let len = match pattern.ascii_table() {
+            Some(table) => rest
+                .iter()
+                .position(|&b| !table.get(b as usize).copied().unwrap_or(false)),
+            None => rest
+                .iter()
+                .position(|&b| !b.is_ascii() || !pattern.clone().matches(b as char)),
+        };
+        self.take_ascii(len.unwrap_or(rest.len()));
+    }
+
+    /// Takes the next `len` bytes, which must all be ASCII.
+    fn take_ascii(&mut self, len: usize) {
+        let bytes = self.document.text.as_bytes();
+        let mut end = self.span.end;
+        let run = &bytes[end.offset..end.offset + len];
+
+        // Only '\n' and '\r' can end a line in ASCII, and most runs have neither.
+        let mut line_start = None;
+        for i in memchr::memchr2_iter(b'\n', b'\r', run) {
+            let followed_by_lf = bytes.get(end.offset + i + 1) == Some(&b'\n');
+            if self
+                .document
+                .line_endings
+                .ends_line(run[i] as char, followed_by_lf)
+            {
+                end.line += 1;
+                line_start = Some(i + 1);
+            }
+        }
+        end.column = match line_start {
+            Some(start) => len - start,
+            None => end.column + len,
+        };
+        end.offset += len;
+        self.span.end = end;
+    }
+
+    pub fn take_until<P: CharPattern + Clone>(&mut self, pattern: P) -> Result<(), TextError> {
+        while !self.peek(pattern.clone()) {
+            self.take()?;
+        }
+        Ok(())
+    }
+
+    pub fn advance(&mut self) -> Span {
+        let new_end = self.span.at_end();
+        std::mem::replace(&mut self.span, new_end)
+    }
+
+    pub fn complete<T>(&mut self, value: T) -> Spanned<T> {
+        Spanned::new(value, self.advance())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(value: T, span: Span) -> Spanned<T> {
+        Spanned { value, span }
+    }
+}
+
+impl<T, U: PartialEq<T>> PartialEq<Spanned<T>> for Spanned<U> {
+    fn eq(&self, other: &Spanned<T>) -> bool {
+        self.span == other.span && self.value == other.value
+    }
+}
+
+impl<U: Eq> Eq for Spanned<U> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    pub fn span_append_contiguous() {
+        let left = Span::new((0, 0, 0).into(), (1, 0, 1).into());
+        let right = Span::new((1, 0, 1).into(), (2, 1, 0).into());
+        let merged = left.append(right).unwrap();
+        assert_eq!(merged.start, (0, 0, 0).into());
+        assert_eq!(merged.end, (2, 1, 0).into());
+    }
+
+    #[test]
+    pub fn span_append_non_contiguous() {
+        let left = Span::new((0, 0, 0).into(), (1, 0, 1).into());
+        let right = Span::new((2, 0, 2).into(), (3, 1, 0).into());
+        assert_eq!(left.append(right), Err(NonContiguousSpansError));
+    }
+
+    #[test]
+    pub fn span_append_other_file() {
+        let mut sources = SourceDatabase::new();
+        let file = sources.add("a.spz", "");
+        let left = Span::new((0, 0, 0).into(), (1, 0, 1).into());
+        let right = Span::in_file(file, (1, 0, 1).into(), (2, 1, 0).into());
+        assert_eq!(left.append(right), Err(NonContiguousSpansError));
+    }
+
+    #[test]
+    pub fn document_apply() {
+        let mut sources = SourceDatabase::new();
+        let file = sources.add("a.spz", "");
+        let mut doc = Document::with_line_endings("a\nb✨c", LineEndings::Lf);
+        doc.file = file;
+        assert_eq!(doc.line_index().line_count(), 2);
+
+        doc.apply(&TextEdit::new(2..6, "x\ny"));
+        assert_eq!(doc.text, "a\nx\nyc");
+        assert_eq!(doc.line_index().line_count(), 3);
+        assert_eq!(doc.file(), file);
+        assert_eq!(doc.line_endings(), LineEndings::Lf);
+    }
+
+    #[test]
+    pub fn document_from_bytes() {
+        let doc = Document::from_bytes(b"a\xFFb\n\xE2\x82c\xF0\x9F");
+        assert_eq!(doc.text, "a\u{FFFD}b\n\u{FFFD}c\u{FFFD}");
+
+        let invalid: Vec<_> = doc
+            .invalid_utf8()
+            .iter()
+            .map(|i| (i.bytes.clone(), i.source_offset, i.offset))
+            .collect();
+        assert_eq!(
+            invalid,
+            vec![
+                (vec![0xFF], 1, 1),
+                (vec![0xE2, 0x82], 4, 6),
+                (vec![0xF0, 0x9F], 7, 10),
+            ]
+        );
+        assert_eq!(
+            doc.invalid_utf8()[1].to_string(),
+            "invalid UTF-8 sequence 0xE2 0x82 at byte 4"
+        );
+        assert_eq!(doc.invalid_utf8_at(6), Some(&doc.invalid_utf8()[1]));
+        assert_eq!(doc.invalid_utf8_at(5), None);
+        assert!(Document::from_bytes("✨".as_bytes())
+            .invalid_utf8()
+            .is_empty());
+    }
+
+    #[test]
+    pub fn document_bom() {
+        let doc = Document::new("\u{FEFF}func");
+        assert_eq!(doc.text, "func");
+        assert!(doc.has_bom());
+        assert!(!Document::new("func\u{FEFF}").has_bom());
+
+        let doc = Document::from_bytes(b"\xEF\xBB\xBFa\xFF");
+        assert_eq!(doc.text, "a\u{FFFD}");
+        assert!(doc.has_bom());
+        assert_eq!(doc.invalid_utf8()[0].source_offset, 4);
+        assert_eq!(doc.invalid_utf8()[0].offset, 1);
+    }
+
+    #[test]
+    pub fn document_apply_moves_invalid_utf8() {
+        let mut doc = Document::from_bytes(b"\xFF \xFF \xFF");
+        doc.apply(&TextEdit::new(3..8, "xy"));
+        assert_eq!(doc.text, "\u{FFFD}xy\u{FFFD}");
+        let offsets: Vec<_> = doc.invalid_utf8().iter().map(|i| i.offset).collect();
+        assert_eq!(offsets, vec![0, 5]);
+    }
+
+    #[test]
+    pub fn window_invalid_utf8() {
+        let doc = Document::from_bytes(b"ab\xFFc");
+        let mut win = Window::new(&doc);
+        win.take_while(char::is_alphabetic).unwrap();
+        assert_eq!(win.content(), "ab");
+        assert_eq!(win.next(), None);
+        assert!(matches!(win.take(), Err(TextError::InvalidUtf8(i)) if i.bytes == [0xFF]));
+        assert_eq!(win.span().end, (5, 0, 3).into());
+        assert_eq!(win.take(), Ok('c'));
+    }
+
+    #[test]
+    pub fn text_edit_apply_at() {
+        let edit = TextEdit::new(12..14, "xyz");
+        assert_eq!(edit.apply_at("{ 42 }", 10), "{ xyz }");
+    }
+
+    #[test]
+    pub fn span_merge() {
+        let left = Span::new((0, 0, 0).into(), (1, 0, 1).into());
+        let right = Span::new((4, 1, 0).into(), (6, 1, 2).into());
+        let merged = Span::new((0, 0, 0).into(), (6, 1, 2).into());
+        assert_eq!(left.merge(right), merged);
+        assert_eq!(right.merge(left), merged);
+    }
+
+    #[test]
+    pub fn empty_window() {
+        let doc = Document::new("this is a test document");
+        let win = Window::new(&doc);
+        assert_eq!(win.content(), "");
+        assert_eq!(win.span().start, (0, 0, 0).into());
+        assert_eq!(win.span().end, (0, 0, 0).into());
+    }
+
+    #[test]
+    pub fn window_take() {
+        let doc = Document::new("this is a test document");
+        let mut win = Window::new(&doc);
+
+        assert_eq!(win.take(), Ok('t'));
+        assert_eq!(win.take(), Ok('h'));
+        assert_eq!(win.take(), Ok('i'));
+        assert_eq!(win.take(), Ok('s'));
+
+        assert_eq!(win.content(), "this");
+        assert_eq!(win.span().start, (0, 0, 0).into());
+        assert_eq!(win.span().end, (4, 0, 4).into());
+    }
+
+    #[test]
+    pub fn window_take_emoji() {
+        let doc = Document::new("✨a");
+        let mut win = Window::new(&doc);
+
+        assert_eq!(win.take(), Ok('✨'));
+        assert_eq!(win.take(), Ok('a'));
+        assert_eq!(win.span().start, (0, 0, 0).into());
+        assert_eq!(win.span().end, (4, 0, 2).into());
+    }
+
+    #[test]
+    pub fn window_take_new_lines() {
+        let doc = Document::new("this\r\nis\na\rtest");
+        let mut win = Window::new(&doc);
+        assert_eq!(win.span().start, (0, 0, 0).into());
+
+        win.take_many(6).unwrap();
+        assert_eq!(win.content(), "this\r\n");
+        assert_eq!(win.span().end, (6, 1, 0).into());
+
+        win.take_many(3).unwrap();
+        assert_eq!(win.content(), "this\r\nis\n");
+        assert_eq!(win.span().end, (9, 2, 0).into());
+
+        win.take_many(6).unwrap();
+        assert_eq!(win.content(), "this\r\nis\na\rtest");
+        assert_eq!(win.span().end, (15, 3, 4).into());
+    }
+
+    #[test]
+    pub fn window_take_crlf() {
+        let doc = Document::new("a\r\nb");
+        let mut win = Window::new(&doc);
+
+        win.take_many(2).unwrap();
+        assert_eq!(win.span().end, (2, 0, 2).into());
+        win.take().unwrap();
+        assert_eq!(win.span().end, (3, 1, 0).into());
+    }
+
+    #[test]
+    pub fn window_take_unicode_new_lines() {
+        let doc = Document::new("a\u{2028}b\u{2029}c\u{85}d");
+        let mut win = Window::new(&doc);
+
+        win.take_many(2).unwrap();
+        assert_eq!(win.span().end, (4, 1, 0).into());
+        win.take_many(2).unwrap();
+        assert_eq!(win.span().end, (8, 2, 0).into());
+        win.take_many(3).unwrap();
+        assert_eq!(win.span().end, (12, 3, 1).into());
+    }
+
+    #[test]
+    pub fn window_take_new_lines_lf_only() {
+        let doc = Document::with_line_endings("this\r\nis\na\rtest\u{2028}", LineEndings::Lf);
+        let mut win = Window::new(&doc);
+
+        win.take_many(6).unwrap();
+        assert_eq!(win.span().end, (6, 1, 0).into());
+        win.take_many(10).unwrap();
+        assert_eq!(win.span().end, (18, 2, 7).into());
+    }
+
+    #[test]
+    pub fn window_take_new_lines_ascii() {
+        let doc = Document::with_line_endings("a\rb\u{2028}c", LineEndings::Ascii);
+        let mut win = Window::new(&doc);
+
+        win.take_many(5).unwrap();
+        assert_eq!(win.span().end, (7, 1, 3).into());
+    }
+
+    #[test]
+    pub fn window_agrees_with_line_index() {
+        let text = "a\r\nb\rc\nd\u{2028}e\u{85}\r\n\n✨";
+        for policy in [LineEndings::Lf, LineEndings::Ascii, LineEndings::Unicode].iter() {
+            let doc = Document::with_line_endings(text, *policy);
+            let mut win = Window::new(&doc);
+            while win.take().is_ok() {
+                let end = win.span().end;
+                assert_eq!(doc.line_index().location(end.offset), end, "{:?}", policy);
+            }
+        }
+    }
+
+    #[test]
+    pub fn window_take_out_of_bounds() {
+        let doc = Document::new("this is a test document");
+        let mut win = Window::new(&doc);
+        assert_eq!(win.take_many(64), Err(TextError::OutOfBounds));
+    }
+
+    #[test]
+    pub fn window_advance() {
+        let doc = Document::new("this is a test document");
+        let mut win = Window::new(&doc);
+
+        win.take_many(4).unwrap();
+        assert_eq!(win.content(), "this");
+        assert_eq!(win.span().start, (0, 0, 0).into());
+        assert_eq!(win.span().end, (4, 0, 4).into());
+
+        win.advance();
+        win.take_many(3).unwrap();
+        assert_eq!(win.content(), " is");
+        assert_eq!(win.span().start, (4, 0, 4).into());
+        assert_eq!(win.span().end, (7, 0, 7).into());
+
+        win.advance();
+        win.take_many(2).unwrap();
+        assert_eq!(win.content(), " a");
+        assert_eq!(win.span().start, (7, 0, 7).into());
+        assert_eq!(win.span().end, (9, 0, 9).into());
+    }
+
+    #[test]
+    pub fn window_complete() {
+        let doc = Document::new("this is a test document");
+        let mut win = Window::new(&doc);
+
+        win.take_many(4).unwrap();
+        let ret = win.complete((42, "skidoo"));
+
+        assert_eq!(win.content(), "");
+        assert_eq!(win.span().start, (4, 0, 4).into());
+        assert_eq!(win.span().end, (4, 0, 4).into());
+
+        assert_eq!(ret.span.start, (0, 0, 0).into());
+        assert_eq!(ret.span.end, (4, 0, 4).into());
+        assert_eq!(ret.value, (42, "skidoo"));
+    }
+
+    #[test]
+    pub fn window_peek() {
+        let doc = Document::new("this is a");
+        let mut win = Window::new(&doc);
+
+        assert!(win.peek('t'));
+        win.take_many(4).unwrap();
+        assert!(!win.peek('i'));
+        assert!(win.peek(char::is_whitespace));
+    }
+
+    #[test]
+    pub fn window_peek_emoji() {
+        let doc = Document::new("a✨a");
+        let mut win = Window::new(&doc);
+
+        assert!(!win.peek('✨'));
+        win.take().unwrap();
+        assert!(win.peek('✨'));
+    }
+
+    #[test]
+    pub fn window_take_while() {
+        let doc = Document::new("aaaab");
+        let mut win = Window::new(&doc);
+
+        assert_eq!(win.take_while('a'), Ok(()));
+        assert_eq!(win.content(), "aaaa");
+        assert_eq!(win.span().start, (0, 0, 0).into());
+        assert_eq!(win.span().end, (4, 0, 4).into());
+    }
+
+    #[test]
+    pub fn window_take_while_range() {
+        let doc = Document::new("1234test");
+        let mut win = Window::new(&doc);
+
+        assert_eq!(win.take_while('0'..='9'), Ok(()));
+        assert_eq!(win.content(), "1234");
+        assert_eq!(win.span().start, (0, 0, 0).into());
+        assert_eq!(win.span().end, (4, 0, 4).into());
+    }
+
+    #[test]
+    pub fn window_take_while_emoji() {
+        let doc = Document::new("✨✨✨✨b");
+        let mut win = Window::new(&doc);
+
+        assert_eq!(win.take_while('✨'), Ok(()));
+        assert_eq!(win.content(), "✨✨✨✨");
+        assert_eq!(win.span().start, (0, 0, 0).into());
+        assert_eq!(win.span().end, (12, 0, 4).into());
+    }
+
+    #[test]
+    pub fn window_take_while_fn() {
+        let doc = Document::new("this is a test document");
+        let mut win = Window::new(&doc);
+
+        assert_eq!(win.take_while(char::is_alphabetic), Ok(()));
+        assert_eq!(win.content(), "this");
+        assert_eq!(win.span().start, (0, 0, 0).into());
+        assert_eq!(win.span().end, (4, 0, 4).into());
+    }
+
+    #[test]
+    pub fn window_take_until() {
+        let doc = Document::new("this is a test document");
+        let mut win = Window::new(&doc);
+
+        assert_eq!(win.take_until(' '), Ok(()));
+        assert_eq!(win.content(), "this");
+        assert_eq!(win.span().start, (0, 0, 0).into());
+        assert_eq!(win.span().end, (4, 0, 4).into());
+    }
+
+    #[test]
+    pub fn window_take_until_range() {
+        let doc = Document::new("this42");
+        let mut win = Window::new(&doc);
+
+        assert_eq!(win.take_until('0'..='9'), Ok(()));
+        assert_eq!(win.content(), "this");
+        assert_eq!(win.span().start, (0, 0, 0).into());
+        assert_eq!(win.span().end, (4, 0, 4).into());
+    }
+
+    #[test]
+    pub fn window_take_until_emoji() {
+        let doc = Document::new("aaaa✨");
+        let mut win = Window::new(&doc);
+
+        assert_eq!(win.take_until('✨'), Ok(()));
+        assert_eq!(win.content(), "aaaa");
+        assert_eq!(win.span().start, (0, 0, 0).into());
+        assert_eq!(win.span().end, (4, 0, 4).into());
+    }
+
+    #[test]
+    pub fn window_take_until_fn() {
+        let doc = Document::new("this is a test document");
+        let mut win = Window::new(&doc);
+
+        assert_eq!(win.take_until(char::is_whitespace), Ok(()));
+        assert_eq!(win.content(), "this");
+        assert_eq!(win.span().start, (0, 0, 0).into());
+        assert_eq!(win.span().end, (4, 0, 4).into());
+    }
+
+    /// Generates the text of a random document from pieces chosen to cover every line ending and
+    /// characters of each UTF-8 width, with any character at all mixed in.
+    fn text() -> impl Strategy<Value = (String, LineEndings)> {
+        let piece = prop_oneof![
+            prop::sample::select(vec![
+                "a",
+                " ",
+                "\n",
+                "\r\n",
+                "\r",
+                "\u{85}",
+                "\u{2028}",
+                "\u{2029}",
+                "é",
+                "e\u{301}",
+                "京",
+                "𝔸",
+                "👩\u{200D}👧",
+                "\u{FEFF}",
+            ])
+            .prop_map(str::to_owned),
+            any::<char>().prop_map(String::from),
+        ];
+        let line_endings = prop::sample::select(vec![
+            LineEndings::Lf,
+            LineEndings::Ascii,
+            LineEndings::Unicode,
+        ]);
+        (prop::collection::vec(piece, 0..64), line_endings)
+            .prop_map(|(pieces, line_endings)| (pieces.concat(), line_endings))
+    }
+
+    /// Skips `skip` characters of `document`, then takes the run matching `pattern`.
+    fn take_while<P: CharPattern + Clone>(document: &Document, skip: usize, pattern: P) -> Span {
+        let mut window = Window::new(document);
+        let _ = window.take_many(skip);
+        window.take_while(pattern).unwrap();
+        window.span()
+    }
+
+    /// Skips `skip` characters of `document`, then takes the characters matching `pattern` one at
+    /// a time.
+    fn take_each(document: &Document, skip: usize, pattern: impl Fn(char) -> bool) -> Span {
+        let mut window = Window::new(document);
+        let _ = window.take_many(skip);
+        while window.peek(&pattern) {
+            window.take().unwrap();
+        }
+        window.span()
+    }
+
+    /// Finds the location of `offset` the slow way, by walking the text from the start.
+    fn reference_location(document: &Document, offset: usize) -> Location {
+        let breaks: &[&str] = match document.line_endings() {
+            LineEndings::Lf => &["\n"],
+            LineEndings::Ascii => &["\r\n", "\n", "\r"],
+            LineEndings::Unicode => &["\r\n", "\n", "\r", "\u{85}", "\u{2028}", "\u{2029}"],
+        };
+        let text = &document.text;
+        let (mut at, mut line, mut column) = (0, 0, 0);
+        while at < offset {
+            let rest = &text[at..];
+            match breaks.iter().find(|b| rest.starts_with(**b)) {
+                Some(b) if at + b.len() <= offset => {
+                    at += b.len();
+                    line += 1;
+                    column = 0;
+                }
+                _ => {
+                    at += rest.chars().next().unwrap().len_utf8();
+                    column += 1;
+                }
+            }
+        }
+        Location::new(offset, line, column)
+    }
+
+    proptest! {
+        /// Takes the document in random-sized pieces, checking each piece's span against the text
+        /// and the reference locations, and that the pieces join back into the whole.
+        #[test]
+        fn window_spans_cover_text(
+            (text, line_endings) in text(),
+            counts in prop::collection::vec(0..8usize, 0..32),
+        ) {
+            let document = Document::with_line_endings(text, line_endings);
+            let mut win = Window::new(&document);
+            let mut whole = win.span();
+            let mut content = String::new();
+            for count in counts.into_iter().chain(std::iter::once(usize::MAX)) {
+                let result = win.take_many(count);
+                prop_assert!(result.is_ok() || result == Err(TextError::OutOfBounds));
+
+                let window_content = win.content().to_owned();
+                let span = win.advance();
+                prop_assert_eq!(document.text_at(span), &window_content[..]);
+                for location in [span.start, span.end] {
+                    prop_assert_eq!(location, reference_location(&document, location.offset));
+                    prop_assert_eq!(location, document.line_index().location(location.offset));
+                }
+
+                whole = whole.append(span).unwrap();
+                content.push_str(&window_content);
+                prop_assert_eq!(document.text_at(whole), &content[..]);
+            }
+            prop_assert_eq!(content, document.text.clone());
+        }
+
+        /// Taking a run with `take_while`, which scans ASCII in bulk, ends where taking one
+        /// character at a time does, for patterns with and without an ASCII table.
+        #[test]
+        fn take_while_matches_take(
+            (text, line_endings) in text(),
+            skip in 0..8usize,
+        ) {
+            let document = Document::with_line_endings(text, line_endings);
+            let function = |c: char| c != 'a' && c != '\u{2028}';
+            let class = CharClass::all_but(b"a", |c| c != '\u{2028}');
+            let slow = take_each(&document, skip, function);
+            prop_assert_eq!(take_while(&document, skip, class), slow);
+            prop_assert_eq!(take_while(&document, skip, function), slow);
+            prop_assert_eq!(
+                take_while(&document, skip, CharClass::WHITESPACE),
+                take_each(&document, skip, char::is_whitespace)
+            );
+        }
+
+        /// Applying an edit updates the line index to the one built from scratch for the new
+        /// text.
+        #[test]
+        fn line_index_follows_edits(
+            (text, line_endings) in text(),
+            (replacement, _) in text(),
+            range in prop::array::uniform2(any::<prop::sample::Index>()),
+        ) {
+            let mut document = Document::with_line_endings(text, line_endings);
+            let boundaries: Vec<usize> = document
+                .text
+                .char_indices()
+                .map(|(offset, _)| offset)
+                .chain(std::iter::once(document.text.len()))
+                .collect();
+            let [start, end] = range.map(|i| *i.get(&boundaries));
+            let edit = TextEdit::new(start.min(end)..start.max(end), replacement);
+
+            document.line_index();
+            document.apply(&edit);
+            let expected = LineIndex::new(&document.text, line_endings);
+            prop_assert_eq!(document.line_index(), &expected, "after {:?}", edit);
+        }
+
+        /// Merging spans gives the span from the earliest start to the latest end, in either order,
+        /// and appending agrees with merging when the spans meet.
+        #[test]
+        fn span_merge_and_append(
+            (text, line_endings) in text(),
+            offsets in prop::array::uniform4(any::<prop::sample::Index>()),
+        ) {
+            let document = Document::with_line_endings(text, line_endings);
+            let boundaries: Vec<usize> = document
+                .text
+                .char_indices()
+                .map(|(offset, _)| offset)
+                .chain(std::iter::once(document.text.len()))
+                .collect();
+            let mut offsets = offsets.map(|i| *i.get(&boundaries));
+            let location = |offset| document.line_index().location(offset);
+            let span = |start, end| Span::new(location(start), location(end));
+
+            let ordered = |a: usize, b: usize| span(a.min(b), a.max(b));
+            let (left, right) = (ordered(offsets[0], offsets[1]), ordered(offsets[2], offsets[3]));
+            let merged = left.merge(right);
+            prop_assert_eq!(merged, right.merge(left));
+            prop_assert_eq!(merged.start.offset, left.start.offset.min(right.start.offset));
+            prop_assert_eq!(merged.end.offset, left.end.offset.max(right.end.offset));
+
+            offsets.sort_unstable();
+            let (left, right) = (span(offsets[0], offsets[1]), span(offsets[1], offsets[2]));
+            prop_assert_eq!(left.append(right), Ok(left.merge(right)));
+            prop_assert_eq!(
+                document.text_at(left.merge(right)),
+                format!("{}{}", document.text_at(left), document.text_at(right))
+            );
+        }
+    }
+}
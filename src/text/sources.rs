@@ -0,0 +1,146 @@
+//! A database of every source file taking part in a compilation
+
+use super::{Document, LineEndings, Span};
+
+/// Identifies a document within a `SourceDatabase`.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct FileId(u32);
+
+impl FileId {
+    /// The file of documents that don't belong to any database.
+    pub const DETACHED: FileId = FileId(u32::MAX);
+}
+
+struct SourceFile {
+    name: String,
+    document: Document,
+}
+
+/// Owns the documents of a compilation, so spans from any of them can be traced back to their
+/// text.
+#[derive(Default)]
+pub struct SourceDatabase {
+    files: Vec<SourceFile>,
+    line_endings: LineEndings,
+}
+
+impl SourceDatabase {
+    pub fn new() -> SourceDatabase {
+        SourceDatabase::default()
+    }
+
+    /// Creates a database whose documents use `line_endings`, unless they're added with
+    /// `add_document`.
+    pub fn with_line_endings(line_endings: LineEndings) -> SourceDatabase {
+        SourceDatabase {
+            files: Vec::new(),
+            line_endings,
+        }
+    }
+
+    /// Adds a document using the database's line endings.
+    ///
+    /// `name` identifies the document to the reader, usually its path.
+    pub fn add<N: Into<String>, S: Into<String>>(&mut self, name: N, text: S) -> FileId {
+        self.add_document(name, Document::with_line_endings(text, self.line_endings))
+    }
+
+    /// Adds a document loaded from bytes, with any invalid UTF-8 in them replaced.
+    pub fn add_bytes<N: Into<String>>(&mut self, name: N, bytes: &[u8]) -> FileId {
+        let document = Document::from_bytes_with_line_endings(bytes, self.line_endings);
+        self.add_document(name, document)
+    }
+
+    /// Adds `document`, which from then on produces spans in the returned file.
+    pub fn add_document<N: Into<String>>(&mut self, name: N, mut document: Document) -> FileId {
+        let file = FileId(self.files.len() as u32);
+        assert!(file != FileId::DETACHED, "too many files");
+
+        document.file = file;
+        self.files.push(SourceFile {
+            name: name.into(),
+            document,
+        });
+        file
+    }
+
+    /// Finds the first file added under `name`.
+    pub fn find(&self, name: &str) -> Option<FileId> {
+        self.files
+            .iter()
+            .position(|f| f.name == name)
+            .map(|i| FileId(i as u32))
+    }
+
+    pub fn name(&self, file: FileId) -> Option<&str> {
+        self.get(file).map(|f| f.name.as_str())
+    }
+
+    pub fn document(&self, file: FileId) -> Option<&Document> {
+        self.get(file).map(|f| &f.document)
+    }
+
+    /// Gets the text covered by `span`, from whichever file it belongs to.
+    pub fn text_at(&self, span: Span) -> Option<&str> {
+        self.document(span.file).map(|d| d.text_at(span))
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = FileId> {
+        (0..self.files.len() as u32).map(FileId)
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    fn get(&self, file: FileId) -> Option<&SourceFile> {
+        self.files.get(file.0 as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::Window;
+
+    #[test]
+    pub fn add_files() {
+        let mut sources = SourceDatabase::new();
+        let a = sources.add("a.spz", "func a(): int { 1 }");
+        let b = sources.add("b.spz", "func b(): int { 2 }");
+
+        assert_ne!(a, b);
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources.files().collect::<Vec<_>>(), vec![a, b]);
+        assert_eq!(sources.name(b), Some("b.spz"));
+        assert_eq!(sources.document(a).unwrap().file(), a);
+        assert_eq!(sources.find("a.spz"), Some(a));
+        assert_eq!(sources.find("c.spz"), None);
+        assert!(sources.document(FileId::DETACHED).is_none());
+    }
+
+    #[test]
+    pub fn spans_carry_their_file() {
+        let mut sources = SourceDatabase::new();
+        sources.add("a.spz", "first");
+        let b = sources.add("b.spz", "second");
+
+        let mut win = Window::new(sources.document(b).unwrap());
+        win.take_many(3).unwrap();
+        let span = win.advance();
+        assert_eq!(span.file, b);
+        assert_eq!(sources.text_at(span), Some("sec"));
+        assert_eq!(win.span().file, b);
+    }
+
+    #[test]
+    pub fn detached_documents() {
+        let doc = Document::new("text");
+        assert_eq!(doc.file(), FileId::DETACHED);
+        assert_eq!(Window::new(&doc).span().file, FileId::DETACHED);
+    }
+}
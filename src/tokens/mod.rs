@@ -1,9 +1,9 @@
 use crate::{
     ast::Token,
-    text::{Span, Spanned, TextError, Window},
+    text::{Document, Span, Spanned, TextError, Window},
 };
 
-mod rules;
+pub(crate) mod rules;
 
 #[derive(Debug)]
 pub enum TokenError {
@@ -11,6 +11,8 @@ pub enum TokenError {
     NoCurrentToken,
     InvalidText(TextError),
     InvalidNumber(String),
+    /// An `r#` that isn't followed by an identifier.
+    InvalidRawIdentifier,
     Unexpected(char),
 }
 
@@ -23,51 +25,128 @@ impl From<TextError> for TokenError {
     }
 }
 
+impl std::fmt::Display for TokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TokenError::EndOfFile => write!(f, "unexpected end of file"),
+            TokenError::NoCurrentToken => write!(f, "no current token"),
+            TokenError::InvalidText(e) => write!(f, "{}", e),
+            TokenError::InvalidNumber(s) => write!(f, "invalid number '{}'", s),
+            TokenError::InvalidRawIdentifier => write!(f, "expected an identifier after 'r#'"),
+            TokenError::Unexpected(c) => write!(f, "unexpected character '{}'", c),
+        }
+    }
+}
+
+/// Splits `document` into tokens, comments included. Text that doesn't lex is reported and
+/// skipped, so lexing always reaches the end.
+pub fn tokenize(document: &Document) -> Vec<Result<Spanned<Token>, Spanned<TokenError>>> {
+    let mut window = Window::new(document);
+    let mut tokens = Vec::new();
+    loop {
+        match rules::next_token(&mut window) {
+            Ok(token) => tokens.push(Ok(token)),
+            Err(TokenError::EndOfFile) => break,
+            Err(e) => {
+                // The window still covers the bad text, which the next token will skip.
+                let span = window.span();
+                if span.start == span.end {
+                    break;
+                }
+                tokens.push(Err(Spanned::new(e, span)));
+            }
+        }
+    }
+    tokens
+}
+
+/// Lists `tokens` one per line, each with its span and kind, as `spuzc lex` prints them.
+pub fn dump(tokens: &[Result<Spanned<Token>, Spanned<TokenError>>]) -> String {
+    let mut out = String::new();
+    for token in tokens {
+        let line = match token {
+            Ok(t) => format!(
+                "{:<12} {:<11} {}",
+                t.span,
+                format!("{:?}", t.value.kind()),
+                t.value
+            ),
+            Err(e) => format!("{:<12} error: {}", e.span, e.value),
+        };
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
 pub struct TokenBuffer<'a> {
     window: Window<'a>,
     span: Span,
     content: Vec<Token>,
     buffer: Option<Spanned<Token>>,
+    /// Comments skipped over so far, which never appear as tokens.
+    comments: Vec<Spanned<String>>,
 }
 
 impl<'a> TokenBuffer<'a> {
     pub fn new(window: Window<'a>) -> TokenBuffer<'a> {
         TokenBuffer {
+            span: window.span(),
             window,
-            span: Span::ZERO,
             content: Vec::new(),
             buffer: None,
+            comments: Vec::new(),
         }
     }
 
     pub fn complete(&mut self) -> Spanned<Vec<Token>> {
-        let new_end = Span::new(self.span.end, self.span.end);
+        let new_end = self.span.at_end();
         Spanned::new(
-            std::mem::replace(&mut self.content, Vec::new()),
+            std::mem::take(&mut self.content),
             std::mem::replace(&mut self.span, new_end),
         )
     }
 
     pub fn peek(&mut self) -> Result<&Spanned<Token>, TokenError> {
-        if self.buffer.is_none() {
-            let token = match rules::next_token(&mut self.window) {
-                Ok(spanned) => spanned,
-                Err(e) => return Err(e),
-            };
-            self.buffer = Some(token)
+        while self.buffer.is_none() {
+            let token = rules::next_token(&mut self.window)?;
+            match token.value {
+                Token::Comment(text) => self.comments.push(Spanned::new(text, token.span)),
+                value => self.buffer = Some(Spanned::new(value, token.span)),
+            }
         }
         self.buffer.as_ref().ok_or(TokenError::EndOfFile)
     }
 
+    /// Gets the span of the text the lexer last looked at, which after an error is the text it
+    /// couldn't make sense of.
+    pub fn pending(&self) -> Span {
+        self.window.span()
+    }
+
+    /// Takes the comments skipped over since the last call.
+    pub fn take_comments(&mut self) -> Vec<Spanned<String>> {
+        std::mem::take(&mut self.comments)
+    }
+
+    /// Takes the next token and returns it directly, without adding it to the buffered content.
+    pub fn take_token(&mut self) -> Result<Spanned<Token>, TokenError> {
+        self.peek()?;
+        self.buffer.take().ok_or(TokenError::NoCurrentToken)
+    }
+
+    /// Takes the next token into the buffered content, whose span grows to cover it along with any
+    /// whitespace or comments before it.
     pub fn take(&mut self) -> Result<(), TokenError> {
         self.peek()?;
 
         match self.buffer.take() {
             Some(s) => {
-                self.span = self
-                    .span
-                    .append(s.span)
-                    .expect("Window returned non-contiguous span!");
+                self.span = if self.content.is_empty() {
+                    s.span
+                } else {
+                    self.span.merge(s.span)
+                };
                 self.content.push(s.value);
                 Ok(())
             }
@@ -119,6 +198,103 @@ mod tests {
         )
     }
 
+    #[test]
+    pub fn token_buffer_take_spaced() {
+        let doc = Document::new("  ( // one\n 42 ) x");
+        let mut buf = TokenBuffer::new(Window::new(&doc));
+
+        buf.take().unwrap();
+        buf.take().unwrap();
+        buf.take().unwrap();
+        assert_eq!(
+            buf.complete(),
+            Spanned::new(
+                vec![Token::LParen, Token::Integer(42), Token::RParen],
+                Span::new((2, 0, 2).into(), (16, 1, 5).into())
+            )
+        );
+    }
+
+    #[test]
+    pub fn token_buffer_take_token() {
+        let content = "( 42)";
+        let doc = Document::new(content);
+        let win = Window::new(&doc);
+        let mut buf = TokenBuffer::new(win);
+
+        assert_eq!(
+            buf.take_token().unwrap(),
+            Spanned::new(Token::LParen, Span::new((0, 0, 0).into(), (1, 0, 1).into()))
+        );
+        assert_eq!(
+            buf.take_token().unwrap(),
+            Spanned::new(
+                Token::Integer(42),
+                Span::new((2, 0, 2).into(), (4, 0, 4).into())
+            )
+        );
+        assert_eq!(buf.complete(), Spanned::new(vec![], Span::ZERO));
+    }
+
+    #[test]
+    pub fn token_buffer_comments() {
+        let content = "// first\n( // second\n42";
+        let doc = Document::new(content);
+        let win = Window::new(&doc);
+        let mut buf = TokenBuffer::new(win);
+
+        assert_eq!(buf.take_token().unwrap().value, Token::LParen);
+        assert_eq!(buf.take_token().unwrap().value, Token::Integer(42));
+        assert!(matches!(buf.take_token(), Err(TokenError::EndOfFile)));
+        assert_eq!(
+            buf.take_comments(),
+            vec![
+                Spanned::new(
+                    " first".to_owned(),
+                    Span::new((0, 0, 0).into(), (8, 0, 8).into())
+                ),
+                Spanned::new(
+                    " second".to_owned(),
+                    Span::new((11, 1, 2).into(), (20, 1, 11).into())
+                ),
+            ]
+        );
+        assert!(buf.take_comments().is_empty());
+    }
+
+    #[test]
+    pub fn tokenize_everything() {
+        let doc = Document::new("func ✨ f // hi\n  42");
+        let tokens: Vec<_> = tokenize(&doc)
+            .into_iter()
+            .map(|t| match t {
+                Ok(t) => format!("{} {}", t.span, t.value),
+                Err(e) => format!("{} error: {}", e.span, e.value),
+            })
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                "1:1-1:5 func",
+                "1:6-1:7 error: unexpected character '✨'",
+                "1:8-1:9 f",
+                "1:10-1:15 // hi",
+                "2:3-2:5 42",
+            ]
+        );
+    }
+
+    #[test]
+    pub fn dump_tokens() {
+        let tokens = tokenize(&Document::new("f( ✨"));
+        assert_eq!(
+            dump(&tokens),
+            "1:1-1:2      Identifier  f\n\
+             1:2-1:3      LParen      (\n\
+             1:4-1:5      error: unexpected character '✨'\n"
+        );
+    }
+
     #[test]
     pub fn token_buffer_complete() {
         let content = "(42)";
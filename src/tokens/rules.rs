@@ -1,23 +1,89 @@
-use crate::text::{Spanned, Window};
+use unicode_ident::{is_xid_continue, is_xid_start};
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+use crate::symbol::Symbol;
+use crate::text::{CharClass, Spanned, Window};
 use crate::tokens::{Token, TokenError};
 
 pub fn next_token(window: &mut Window) -> Result<Spanned<Token>, TokenError> {
+    if after_invalid_in_comment(window) && window.next().is_some() && !window.at_line_end() {
+        // The comment was cut short to report the invalid UTF-8, and carries on after it.
+        window.advance();
+        rest_of_line(window)?;
+        let text = window.content().trim_end().to_owned();
+        return Ok(window.complete(Token::Comment(text)));
+    }
+
     // Discard whitespace
-    window.take_while(char::is_whitespace)?;
+    window.take_while(CharClass::WHITESPACE)?;
     window.advance();
 
     match window.take()? {
         '(' => Ok(window.complete(Token::LParen)),
         ')' => Ok(window.complete(Token::RParen)),
         ':' => Ok(window.complete(Token::Colon)),
+        ';' => Ok(window.complete(Token::Semicolon)),
+        '.' => Ok(window.complete(Token::Dot)),
         '{' => Ok(window.complete(Token::LBrace)),
         '}' => Ok(window.complete(Token::RBrace)),
+        '/' if window.peek('/') => comment(window),
         '0'..='9' => number(window),
-        x if x.is_alphabetic() || x == '_' => ident(window),
+        'r' if window.peek('#') => raw_ident(window),
+        x if is_ident_start(x) => ident(window),
         x => Err(TokenError::Unexpected(x)),
     }
 }
 
+/// Everything but the characters that can end a line. Whether they do depends on the document.
+const NOT_LINE_END: CharClass = CharClass::all_but(b"\n\r", |c| {
+    !matches!(c, '\u{85}' | '\u{2028}' | '\u{2029}')
+});
+
+/// The characters that can carry on an identifier.
+const IDENT_CONTINUE: CharClass = CharClass::new(
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ_abcdefghijklmnopqrstuvwxyz",
+    is_xid_continue,
+);
+
+fn comment(window: &mut Window) -> Result<Spanned<Token>, TokenError> {
+    rest_of_line(window)?;
+    let text = window.content()["//".len()..].trim_end().to_owned();
+    Ok(window.complete(Token::Comment(text)))
+}
+
+/// Takes the rest of the line, stopping before whatever ends it under the document's line
+/// endings.
+fn rest_of_line(window: &mut Window) -> Result<(), TokenError> {
+    loop {
+        window.take_while(NOT_LINE_END)?;
+        if window.next().is_none() || window.at_line_end() {
+            return Ok(());
+        }
+        window.take()?;
+    }
+}
+
+/// Determines if the window has just passed invalid UTF-8 within a comment. There are no string
+/// literals, so anything after a `//` on the same line is in one.
+fn after_invalid_in_comment(window: &Window) -> bool {
+    let document = window.document();
+    let invalid = match window
+        .span()
+        .end
+        .offset
+        .checked_sub(char::REPLACEMENT_CHARACTER.len_utf8())
+    {
+        Some(offset) if document.invalid_utf8_at(offset).is_some() => offset,
+        _ => return false,
+    };
+
+    let lines = document.line_index();
+    let start = lines
+        .line_range(lines.line(invalid))
+        .map_or(0, |range| range.start);
+    document.text[start..invalid].contains("//")
+}
+
 fn number(window: &mut Window) -> Result<Spanned<Token>, TokenError> {
     window.take_while('0'..='9')?;
     let s = window.content().to_owned();
@@ -27,20 +93,51 @@ fn number(window: &mut Window) -> Result<Spanned<Token>, TokenError> {
     }
 }
 
+/// Determines if `c` can start an identifier. Identifiers follow UAX #31, starting with a character
+/// in `XID_Start` or an underscore and carrying on with characters in `XID_Continue`.
+fn is_ident_start(c: char) -> bool {
+    c == '_' || is_xid_start(c)
+}
+
 fn ident(window: &mut Window) -> Result<Spanned<Token>, TokenError> {
-    window.take_while(|c: char| c.is_alphanumeric() || c == '_')?;
-    let tok = match window.content() {
-        "func" => Token::Func,
-        "int" => Token::Int,
-        x => Token::Identifier(x.to_owned()),
+    window.take_while(IDENT_CONTINUE)?;
+    let tok = match symbol(window, 0) {
+        Symbol::FUNC => Token::Func,
+        Symbol::INT => Token::Int,
+        Symbol::MODULE => Token::Module,
+        Symbol::IMPORT => Token::Import,
+        Symbol::PUB => Token::Pub,
+        x => Token::Identifier(x),
     };
     Ok(window.complete(tok))
 }
 
+/// Lexes a raw identifier like `r#func`, which is a name even if it's spelled like a keyword.
+fn raw_ident(window: &mut Window) -> Result<Spanned<Token>, TokenError> {
+    window.take()?;
+    if !window.peek(is_ident_start) {
+        return Err(TokenError::InvalidRawIdentifier);
+    }
+    window.take_while(IDENT_CONTINUE)?;
+    let symbol = symbol(window, "r#".len());
+    Ok(window.complete(Token::Identifier(symbol)))
+}
+
+/// Interns the identifier in the window, skipping the first `prefix` bytes.
+fn symbol(window: &Window, prefix: usize) -> Symbol {
+    let name = &window.content()[prefix..];
+    if window.document().normalizes_identifiers() && is_nfc_quick(name.chars()) != IsNormalized::Yes
+    {
+        Symbol::intern(&name.nfc().collect::<String>())
+    } else {
+        Symbol::intern(name)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::text::{Document, Window};
+    use crate::text::{CharPattern, Document, LineEndings, TextError, Window};
 
     macro_rules! complete_token_test {
         ($name: ident, $content: expr, $token: expr) => {
@@ -67,17 +164,170 @@ mod tests {
     complete_token_test!(colon, ":", Token::Colon);
     complete_token_test!(lbrace, "{", Token::LBrace);
     complete_token_test!(rbrace, "}", Token::RBrace);
+    complete_token_test!(semicolon, ";", Token::Semicolon);
+    complete_token_test!(dot, ".", Token::Dot);
     complete_token_test!(ident_alpha, "ident", Token::Identifier("ident".into()));
     complete_token_test!(
         ident_alpha_num,
         "ident42",
         Token::Identifier("ident42".into())
     );
-    complete_token_test!(ident_unicode, "京¾৬", Token::Identifier("京¾৬".into()));
-    complete_token_test!(ident_underscore, "_¾৬", Token::Identifier("_¾৬".into()));
+    complete_token_test!(ident_unicode, "京৬", Token::Identifier("京৬".into()));
+    complete_token_test!(ident_underscore, "_৬", Token::Identifier("_৬".into()));
+    complete_token_test!(
+        ident_combining_mark,
+        "e\u{301}",
+        Token::Identifier("\u{E9}".into())
+    );
+    complete_token_test!(ident_raw, "r#func", Token::Identifier("func".into()));
+    complete_token_test!(ident_raw_name, "r#x", Token::Identifier("x".into()));
+    complete_token_test!(ident_r, "r", Token::Identifier("r".into()));
 
     complete_token_test!(unsigned_integer, "1234", Token::Integer(1234));
 
     complete_token_test!(keyword_func, "func", Token::Func);
     complete_token_test!(keyword_int, "int", Token::Int);
+    complete_token_test!(keyword_module, "module", Token::Module);
+    complete_token_test!(keyword_import, "import", Token::Import);
+    complete_token_test!(keyword_pub, "pub", Token::Pub);
+
+    #[test]
+    pub fn comment() {
+        let doc = Document::new("  // note ✨  \r\n//\nnext");
+        let mut win = Window::new(&doc);
+
+        let tok = next_token(&mut win).unwrap();
+        assert_eq!(tok.value, Token::Comment(" note ✨".into()));
+        assert_eq!(tok.span.start, (2, 0, 2).into());
+        assert_eq!(tok.span.end, (16, 0, 14).into());
+
+        let tok = next_token(&mut win).unwrap();
+        assert_eq!(tok.value, Token::Comment("".into()));
+        assert_eq!(tok.span.start, (17, 1, 0).into());
+
+        assert_eq!(
+            next_token(&mut win).unwrap().value,
+            Token::Identifier("next".into())
+        );
+    }
+
+    #[test]
+    pub fn char_classes() {
+        let check = |class: CharClass, reference: fn(char) -> bool| {
+            let chars = (0..128u8)
+                .map(char::from)
+                .chain("é\u{85}\u{2028}\u{3000}京𝔸✨".chars());
+            for c in chars {
+                assert_eq!(class.matches(c), reference(c), "{:?}", c);
+            }
+        };
+        check(CharClass::WHITESPACE, char::is_whitespace);
+        check(IDENT_CONTINUE, is_xid_continue);
+        check(NOT_LINE_END, |c| {
+            !matches!(c, '\n' | '\r' | '\u{85}' | '\u{2028}' | '\u{2029}')
+        });
+    }
+
+    #[test]
+    pub fn comment_line_endings() {
+        let doc = Document::new("// a\rfunc // b\u{2028}int // c\r\n");
+        let mut win = Window::new(&doc);
+        let tokens: Vec<_> =
+            std::iter::from_fn(|| next_token(&mut win).ok().map(|t| t.value)).collect();
+        assert_eq!(
+            tokens,
+            [
+                Token::Comment(" a".into()),
+                Token::Func,
+                Token::Comment(" b".into()),
+                Token::Int,
+                Token::Comment(" c".into()),
+            ]
+        );
+
+        // Only `\n` ends a line here, so the `\r` is part of the comment.
+        let doc = Document::with_line_endings("// a\rfunc\nint", LineEndings::Lf);
+        let mut win = Window::new(&doc);
+        assert_eq!(
+            next_token(&mut win).unwrap().value,
+            Token::Comment(" a\rfunc".into())
+        );
+        assert_eq!(next_token(&mut win).unwrap().value, Token::Int);
+    }
+
+    #[test]
+    pub fn invalid_utf8() {
+        let doc = Document::from_bytes(b"a\xFFb // c\xFF d\xFF\n\xFF");
+        let mut win = Window::new(&doc);
+        let mut next = || match next_token(&mut win) {
+            Ok(t) => Ok(t.value),
+            Err(TokenError::InvalidText(TextError::InvalidUtf8(i))) => Err(i.source_offset),
+            Err(e) => panic!("unexpected error: {:?}", e),
+        };
+
+        assert_eq!(next(), Ok(Token::Identifier("a".into())));
+        assert_eq!(next(), Err(1));
+        assert_eq!(next(), Ok(Token::Identifier("b".into())));
+        assert_eq!(next(), Ok(Token::Comment(" c".into())));
+        assert_eq!(next(), Err(8));
+        assert_eq!(next(), Ok(Token::Comment(" d".into())));
+        assert_eq!(next(), Err(11));
+        assert_eq!(next(), Err(13));
+    }
+
+    #[test]
+    pub fn ident_normalized() {
+        let mut doc = Document::new("\u{1112}\u{1161}\u{11AB} \u{D55C}");
+        let mut win = Window::new(&doc);
+        let composed = Token::Identifier("\u{D55C}".into());
+        assert_eq!(next_token(&mut win).unwrap().value, composed);
+        assert_eq!(next_token(&mut win).unwrap().value, composed);
+
+        doc.set_normalize_identifiers(false);
+        let mut win = Window::new(&doc);
+        let decomposed = Token::Identifier("\u{1112}\u{1161}\u{11AB}".into());
+        assert_eq!(next_token(&mut win).unwrap().value, decomposed);
+        assert_eq!(next_token(&mut win).unwrap().value, composed);
+    }
+
+    #[test]
+    pub fn bom() {
+        let doc = Document::from_bytes(b"\xEF\xBB\xBFfunc");
+        let tok = next_token(&mut Window::new(&doc)).unwrap();
+        assert_eq!(tok.value, Token::Func);
+        assert_eq!(tok.span.start, (0, 0, 0).into());
+    }
+
+    #[test]
+    pub fn ident_not_xid() {
+        let doc = Document::new("a¾ ৬ r#1");
+        let mut win = Window::new(&doc);
+        assert_eq!(
+            next_token(&mut win).unwrap().value,
+            Token::Identifier("a".into())
+        );
+        assert!(matches!(
+            next_token(&mut win),
+            Err(TokenError::Unexpected('¾'))
+        ));
+        assert!(matches!(
+            next_token(&mut win),
+            Err(TokenError::Unexpected('৬'))
+        ));
+        assert!(matches!(
+            next_token(&mut win),
+            Err(TokenError::InvalidRawIdentifier)
+        ));
+        assert_eq!(win.content(), "r#");
+    }
+
+    #[test]
+    pub fn lone_slash() {
+        let doc = Document::new("/ /");
+        let mut win = Window::new(&doc);
+        assert!(matches!(
+            next_token(&mut win),
+            Err(TokenError::Unexpected('/'))
+        ));
+    }
 }
@@ -0,0 +1,329 @@
+//! Traversing the AST
+//!
+//! There are three ways to go over a module, depending on what a pass needs to do with it:
+//!
+//! - A [`Visitor`] borrows the nodes, so it can collect references to them.
+//! - A [`VisitorMut`] changes nodes in place.
+//! - A [`Fold`] takes the module by value and rebuilds it, so it can replace nodes outright.
+//!
+//! Each has a method per node type whose default goes on to the node's children. A pass overrides
+//! the ones it cares about, and calls the matching walk function to keep going into the children,
+//! or doesn't to skip them.
+
+use crate::{
+    ast::{Block, Expr, Import, Item, Module, SyntaxNode},
+    text::{Span, Spanned},
+};
+
+/// A borrowed AST node of any type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Node<'a> {
+    Module(&'a Module),
+    Import(&'a Spanned<Import>),
+    Item(&'a Spanned<Item>),
+    Block(&'a Spanned<Block>),
+    Expr(&'a Spanned<Expr>),
+}
+
+impl<'a> SyntaxNode for Node<'a> {
+    type Children = Node<'a>;
+    type Iter = std::vec::IntoIter<Node<'a>>;
+
+    /// Gets the span of the node. A module doesn't store one, so it spans from its first part to
+    /// its last, or is `Span::ZERO` if it's empty.
+    fn span(&self) -> Span {
+        match *self {
+            Node::Module(module) => module
+                .name
+                .iter()
+                .map(|n| n.span)
+                .chain(self.children().map(|child| child.span()))
+                .reduce(|a, b| a.merge(b))
+                .unwrap_or(Span::ZERO),
+            Node::Import(import) => import.span,
+            Node::Item(item) => item.span,
+            Node::Block(block) => block.span,
+            Node::Expr(expr) => expr.span,
+        }
+    }
+
+    fn children(&self) -> Self::Iter {
+        let children = match *self {
+            Node::Module(module) => module
+                .imports
+                .iter()
+                .map(Node::Import)
+                .chain(module.items.iter().map(Node::Item))
+                .collect(),
+            Node::Item(item) => match &item.value {
+                Item::Func(decl) => vec![Node::Block(&decl.body)],
+                Item::Error => Vec::new(),
+            },
+            Node::Block(block) => vec![Node::Expr(&block.value.expr)],
+            Node::Import(_) | Node::Expr(_) => Vec::new(),
+        };
+        children.into_iter()
+    }
+}
+
+/// Goes over borrowed nodes, in the order they appear in the source.
+pub trait Visitor<'a> {
+    /// Calls the method for the node's type.
+    fn visit(&mut self, node: Node<'a>) {
+        match node {
+            Node::Module(module) => self.visit_module(module),
+            Node::Import(import) => self.visit_import(import),
+            Node::Item(item) => self.visit_item(item),
+            Node::Block(block) => self.visit_block(block),
+            Node::Expr(expr) => self.visit_expr(expr),
+        }
+    }
+
+    fn visit_module(&mut self, module: &'a Module) {
+        walk(self, Node::Module(module));
+    }
+
+    fn visit_import(&mut self, import: &'a Spanned<Import>) {
+        walk(self, Node::Import(import));
+    }
+
+    fn visit_item(&mut self, item: &'a Spanned<Item>) {
+        walk(self, Node::Item(item));
+    }
+
+    fn visit_block(&mut self, block: &'a Spanned<Block>) {
+        walk(self, Node::Block(block));
+    }
+
+    fn visit_expr(&mut self, expr: &'a Spanned<Expr>) {
+        walk(self, Node::Expr(expr));
+    }
+}
+
+/// Visits each of the node's children.
+pub fn walk<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, node: Node<'a>) {
+    for child in node.children() {
+        visitor.visit(child);
+    }
+}
+
+/// Goes over nodes in place, in the order they appear in the source.
+pub trait VisitorMut {
+    fn visit_module_mut(&mut self, module: &mut Module) {
+        walk_module_mut(self, module);
+    }
+
+    fn visit_import_mut(&mut self, _import: &mut Spanned<Import>) {}
+
+    fn visit_item_mut(&mut self, item: &mut Spanned<Item>) {
+        walk_item_mut(self, item);
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Spanned<Block>) {
+        walk_block_mut(self, block);
+    }
+
+    fn visit_expr_mut(&mut self, _expr: &mut Spanned<Expr>) {}
+}
+
+pub fn walk_module_mut<V: VisitorMut + ?Sized>(visitor: &mut V, module: &mut Module) {
+    for import in &mut module.imports {
+        visitor.visit_import_mut(import);
+    }
+    for item in &mut module.items {
+        visitor.visit_item_mut(item);
+    }
+}
+
+pub fn walk_item_mut<V: VisitorMut + ?Sized>(visitor: &mut V, item: &mut Spanned<Item>) {
+    match &mut item.value {
+        Item::Func(decl) => visitor.visit_block_mut(&mut decl.body),
+        Item::Error => {}
+    }
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, block: &mut Spanned<Block>) {
+    visitor.visit_expr_mut(&mut block.value.expr);
+}
+
+/// Rebuilds a module node by node, bottom up.
+pub trait Fold {
+    fn fold_module(&mut self, module: Module) -> Module {
+        fold_module(self, module)
+    }
+
+    fn fold_import(&mut self, import: Spanned<Import>) -> Spanned<Import> {
+        import
+    }
+
+    fn fold_item(&mut self, item: Spanned<Item>) -> Spanned<Item> {
+        fold_item(self, item)
+    }
+
+    fn fold_block(&mut self, block: Spanned<Block>) -> Spanned<Block> {
+        fold_block(self, block)
+    }
+
+    fn fold_expr(&mut self, expr: Spanned<Expr>) -> Spanned<Expr> {
+        expr
+    }
+}
+
+/// Folds each of the module's imports and items.
+pub fn fold_module<F: Fold + ?Sized>(folder: &mut F, module: Module) -> Module {
+    Module {
+        name: module.name,
+        imports: module
+            .imports
+            .into_iter()
+            .map(|import| folder.fold_import(import))
+            .collect(),
+        items: module
+            .items
+            .into_iter()
+            .map(|item| folder.fold_item(item))
+            .collect(),
+    }
+}
+
+/// Folds a function's body.
+pub fn fold_item<F: Fold + ?Sized>(folder: &mut F, item: Spanned<Item>) -> Spanned<Item> {
+    let value = match item.value {
+        Item::Func(mut decl) => {
+            decl.body = folder.fold_block(decl.body);
+            Item::Func(decl)
+        }
+        Item::Error => Item::Error,
+    };
+    Spanned::new(value, item.span)
+}
+
+/// Folds a block's expression.
+pub fn fold_block<F: Fold + ?Sized>(folder: &mut F, block: Spanned<Block>) -> Spanned<Block> {
+    let expr = folder.fold_expr(block.value.expr);
+    Spanned::new(Block { expr }, block.span)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::Visibility, parser, sexpr, symbol::Symbol, text::Document};
+
+    const SOURCE: &str = "import util;\nfunc main(): int { f() }\npub func f(): int { 1 }";
+
+    fn parse(text: &str) -> Module {
+        parser::parse(&Document::new(text)).unwrap()
+    }
+
+    #[test]
+    pub fn node_children() {
+        let module = parse(SOURCE);
+        let root = Node::Module(&module);
+        assert_eq!(root.span(), Span::new((0, 0, 0).into(), (61, 2, 23).into()));
+
+        let children: Vec<_> = root.children().collect();
+        assert_eq!(
+            children,
+            vec![
+                Node::Import(&module.imports[0]),
+                Node::Item(&module.items[0]),
+                Node::Item(&module.items[1]),
+            ]
+        );
+        assert_eq!(children[1].children().count(), 1);
+        assert_eq!(Node::Module(&Module::default()).span(), Span::ZERO);
+    }
+
+    #[test]
+    pub fn visitor() {
+        #[derive(Default)]
+        struct Calls {
+            public: bool,
+            names: Vec<Symbol>,
+        }
+
+        impl<'a> Visitor<'a> for Calls {
+            fn visit_item(&mut self, item: &'a Spanned<Item>) {
+                if let Item::Func(decl) = &item.value {
+                    self.names.push(decl.name.value);
+                    if self.public && decl.visibility == Visibility::Private {
+                        return;
+                    }
+                }
+                walk(self, Node::Item(item));
+            }
+
+            fn visit_expr(&mut self, expr: &'a Spanned<Expr>) {
+                if let Expr::Call(name) = &expr.value {
+                    self.names.push(name.value);
+                }
+            }
+        }
+
+        let module = parse(SOURCE);
+        let mut calls = Calls::default();
+        calls.visit_module(&module);
+        assert_eq!(calls.names, ["main", "f", "f"]);
+
+        let mut calls = Calls {
+            public: true,
+            ..Calls::default()
+        };
+        calls.visit(Node::Module(&module));
+        assert_eq!(calls.names, ["main", "f"]);
+    }
+
+    #[test]
+    pub fn visitor_mut() {
+        struct Increment;
+
+        impl VisitorMut for Increment {
+            fn visit_expr_mut(&mut self, expr: &mut Spanned<Expr>) {
+                if let Expr::Integer(i) = &mut expr.value {
+                    *i += 1;
+                }
+            }
+        }
+
+        let mut module = parse("func a(): int { 1 }\nfunc b(): int { a() }\nfunc c(): int { 41 }");
+        Increment.visit_module_mut(&mut module);
+        assert_eq!(
+            sexpr::dump(&module, false),
+            sexpr::dump(
+                &parse("func a(): int { 2 }\nfunc b(): int { a() }\nfunc c(): int { 42 }"),
+                false
+            )
+        );
+    }
+
+    #[test]
+    pub fn fold() {
+        // Replaces calls with what the called function returns, if it returns an integer.
+        struct Inline(Module);
+
+        impl Fold for Inline {
+            fn fold_expr(&mut self, expr: Spanned<Expr>) -> Spanned<Expr> {
+                let callee = match &expr.value {
+                    Expr::Call(name) => self.0.functions().find(|d| d.name.value == name.value),
+                    _ => None,
+                };
+                match callee.map(|decl| &decl.body.value.expr.value) {
+                    Some(Expr::Integer(i)) => Spanned::new(Expr::Integer(*i), expr.span),
+                    _ => expr,
+                }
+            }
+        }
+
+        let module = parse(SOURCE);
+        let folded = Inline(module.clone()).fold_module(module.clone());
+        assert_eq!(folded.imports, module.imports);
+        assert_eq!(folded.items[1], module.items[1]);
+        match &folded.items[0].value {
+            Item::Func(decl) => {
+                assert_eq!(decl.body.value.expr.value, Expr::Integer(1));
+                assert_eq!(decl.body.value.expr.span.start.offset, 32);
+            }
+            Item::Error => panic!("expected a function"),
+        }
+    }
+}
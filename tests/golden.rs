@@ -0,0 +1,190 @@
+//! Runs each `.spz` program in `examples/` and `tests/golden/` through the compiler, comparing
+//! what each stage produces with the `.expected` file beside it.
+//!
+//! The expected output has a section for each of the tokens, the syntax tree, the diagnostics from
+//! checking and running the program, and the code `spuzc run` exits with. After changing what the
+//! compiler produces, update the expectations with:
+//!
+//! ```text
+//! cargo test --test golden -- --bless
+//! ```
+//!
+//! Any other argument that isn't a flag limits the run to programs whose path contains it.
+
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+use spuzc::{
+    modules::{self, FileLoader},
+    parser, sexpr,
+    text::{Document, SourceDatabase},
+    tokens,
+};
+
+const DIRECTORIES: &[&str] = &["examples", "tests/golden"];
+const EXTENSION: &str = "expected";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let bless = args.iter().any(|a| a == "--bless");
+    let filters: Vec<&String> = args.iter().filter(|a| !a.starts_with('-')).collect();
+
+    let programs: Vec<PathBuf> = discover()
+        .into_iter()
+        .filter(|p| filters.is_empty() || filters.iter().any(|f| p.to_string_lossy().contains(*f)))
+        .collect();
+
+    println!("\nrunning {} golden tests", programs.len());
+    let mut failures = Vec::new();
+    for program in &programs {
+        let actual = run(program);
+        let expected_path = program.with_extension(EXTENSION);
+        let expected = fs::read_to_string(&expected_path).ok();
+
+        let status = if expected.as_deref() == Some(&actual[..]) {
+            "ok"
+        } else if bless {
+            fs::write(&expected_path, &actual)
+                .unwrap_or_else(|e| panic!("couldn't write {}: {}", expected_path.display(), e));
+            "blessed"
+        } else {
+            failures.push((program, expected, actual));
+            "FAILED"
+        };
+        println!("test {} ... {}", program.display(), status);
+    }
+
+    for (program, expected, actual) in &failures {
+        println!("\n---- {} ----", program.display());
+        match expected {
+            Some(expected) => print!("{}", diff(expected, actual)),
+            None => println!("no {} file, the output was:\n{}", EXTENSION, actual),
+        }
+    }
+    if !failures.is_empty() {
+        println!("\nrun `cargo test --test golden -- --bless` to accept the new output");
+    }
+
+    let result = if failures.is_empty() { "ok" } else { "FAILED" };
+    println!(
+        "\ntest result: {}. {} passed; {} failed\n",
+        result,
+        programs.len() - failures.len(),
+        failures.len()
+    );
+    if !failures.is_empty() {
+        process::exit(1);
+    }
+}
+
+/// Finds the programs directly inside each directory, so modules they import from
+/// subdirectories aren't run on their own.
+fn discover() -> Vec<PathBuf> {
+    let mut programs = Vec::new();
+    for directory in DIRECTORIES {
+        let entries = match fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries {
+            let path = entry.expect("couldn't read directory entry").path();
+            if path.extension().is_some_and(|e| e == "spz") {
+                programs.push(path);
+            }
+        }
+    }
+    programs.sort();
+    programs
+}
+
+/// Runs `path` through each stage, describing what came out of it.
+fn run(path: &Path) -> String {
+    let bytes =
+        fs::read(path).unwrap_or_else(|e| panic!("couldn't read {}: {}", path.display(), e));
+    let document = Document::from_bytes(&bytes);
+    let tokens = tokens::dump(&tokens::tokenize(&document));
+    let (module, _) = parser::parse_recovering(&document);
+    let ast = sexpr::dump(&module, true);
+
+    let (diagnostics, exit_code) = check(path);
+    let diagnostics: Vec<String> = diagnostics.iter().map(|d| format!("{}\n", d)).collect();
+
+    let sections = [
+        ("tokens", tokens),
+        ("ast", ast),
+        ("diagnostics", diagnostics.join("\n")),
+        ("exit code", format!("{}\n", exit_code)),
+    ];
+    let sections: Vec<String> = sections
+        .iter()
+        .map(|(title, body)| format!("== {}\n{}", title, body))
+        .collect();
+    sections.join("\n")
+}
+
+/// Checks and runs the program in `path` the way `spuzc check` and `spuzc run` do, returning
+/// the diagnostics they report and the code `spuzc run` exits with.
+fn check(path: &Path) -> (Vec<String>, i32) {
+    let mut sources = SourceDatabase::new();
+    let mut warnings = Vec::new();
+    let result = modules::check(&mut sources, &mut FileLoader, path, &mut warnings);
+    let mut diagnostics: Vec<String> = warnings.iter().map(|d| d.render(&sources)).collect();
+
+    let program = match result {
+        Ok(program) => program,
+        Err(errors) => {
+            diagnostics.extend(errors.iter().map(|d| d.render(&sources)));
+            return (diagnostics, 1);
+        }
+    };
+    match modules::run(&program, path) {
+        Ok(exit_code) => (diagnostics, exit_code),
+        Err(e) => {
+            diagnostics.push(e.render(&sources));
+            (diagnostics, 1)
+        }
+    }
+}
+
+/// Shows the lines that differ between `expected` and `actual`, marking missing lines with `-`
+/// and unexpected ones with `+`.
+fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+
+    // The longest common subsequence of lines, where `lengths[i][j]` covers the lines from `i`
+    // in `expected` and `j` in `actual` onwards.
+    let mut lengths = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lengths[i][j] = if expected[i] == actual[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            writeln!(out, "  {}", expected[i]).unwrap();
+            i += 1;
+            j += 1;
+        } else if i < expected.len()
+            && (j == actual.len() || lengths[i + 1][j] >= lengths[i][j + 1])
+        {
+            writeln!(out, "- {}", expected[i]).unwrap();
+            i += 1;
+        } else {
+            writeln!(out, "+ {}", actual[j]).unwrap();
+            j += 1;
+        }
+    }
+    out
+}